@@ -0,0 +1,224 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        HeaderMap,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::api::ApiError;
+use crate::AppState;
+
+/// Every issued key starts with this, so a leaked one is easy to search for.
+const KEY_PREFIX: &str = "efk";
+/// Random bytes in a key's secret part.
+const SECRET_BYTES: usize = 32;
+/// Random bytes in a key's id.
+const ID_BYTES: usize = 4;
+
+/// One issued key. Only the hash is kept; the key itself is shown once, when
+/// it's issued.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyEntry {
+    /// Also part of the key, so a key can be revoked without knowing it.
+    pub id: String,
+    /// Who the key was issued to.
+    pub client: String,
+    /// `sha256:` and the hex digest of the whole key.
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// The keys file named by `auth.keys_path`, written by the `keys` subcommand.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyFile {
+    pub keys: Vec<KeyEntry>,
+}
+
+impl KeyFile {
+    /// An empty file when there's none yet, so the first `keys issue` can
+    /// create it.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| format!("Invalid {}: {}", path.display(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(KeyFile::default()),
+            Err(err) => Err(format!("Failed to read {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Writes through a temporary file, so a running server never reads half a
+    /// file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string(self)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, contents)
+            .and_then(|()| std::fs::rename(&temporary, path))
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+    }
+
+    /// Adds a key for `client` and returns it with the key itself.
+    pub fn issue(&mut self, client: &str) -> Result<(KeyEntry, String), String> {
+        let id = loop {
+            let id = random_hex(ID_BYTES)?;
+            if !self.keys.iter().any(|entry| entry.id == id) {
+                break id;
+            }
+        };
+        let key = format!("{}_{}_{}", KEY_PREFIX, id, random_hex(SECRET_BYTES)?);
+        let entry = KeyEntry {
+            id,
+            client: client.to_string(),
+            hash: hash(&key),
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+        self.keys.push(entry.clone());
+        Ok((entry, key))
+    }
+
+    /// Marks the key with this id revoked. Revoked keys stay listed so it's
+    /// clear what happened to them.
+    pub fn revoke(&mut self, id: &str) -> Result<&KeyEntry, String> {
+        let entry = self
+            .keys
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| format!("no key with id '{}'", id))?;
+        if entry.revoked_at.is_some() {
+            return Err(format!("key '{}' is already revoked", id));
+        }
+        entry.revoked_at = Some(Utc::now());
+        Ok(entry)
+    }
+
+    /// The client a key was issued to, if it's a key in this file and not
+    /// revoked.
+    fn client(&self, key: &str) -> Option<&str> {
+        let hash = hash(key);
+        self.keys
+            .iter()
+            .filter(|entry| entry.revoked_at.is_none())
+            .find(|entry| same(entry.hash.as_bytes(), hash.as_bytes()))
+            .map(|entry| entry.client.as_str())
+    }
+}
+
+fn hash(key: &str) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Compares without stopping at the first difference.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn random_hex(bytes: usize) -> Result<String, String> {
+    let mut buf = vec![0; bytes];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut buf))
+        .map_err(|err| format!("Failed to read /dev/urandom: {}", err))?;
+    Ok(buf.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The keys a running server accepts. Re-reads the file whenever it changes,
+/// so keys issued or revoked from the CLI apply without a restart.
+pub struct KeyStore {
+    path: PathBuf,
+    cached: RwLock<(Option<SystemTime>, KeyFile)>,
+}
+
+impl KeyStore {
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let modified = modified(&path);
+        let keys = KeyFile::load(&path)?;
+        Ok(KeyStore {
+            path,
+            cached: RwLock::new((modified, keys)),
+        })
+    }
+
+    fn client(&self, key: &str) -> Option<String> {
+        let modified = modified(&self.path);
+        if self.cached.read().unwrap().0 != modified {
+            match KeyFile::load(&self.path) {
+                Ok(keys) => *self.cached.write().unwrap() = (modified, keys),
+                // Keep the keys we had rather than locking everyone out over
+                // a file caught mid-edit.
+                Err(err) => warn!(error = %err, "Failed to reload keys"),
+            }
+        }
+        self.cached.read().unwrap().1.client(key).map(str::to_owned)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Who made an authenticated request, added to the request's extensions.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub name: String,
+}
+
+/// The key from `Authorization: Bearer`, `X-API-Key` or, for browsers that
+/// can't set headers on a WebSocket, the `api_key` query parameter.
+fn presented_key<'a>(headers: &'a HeaderMap, query: Option<&'a str>) -> Option<&'a str> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let header = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok());
+    let param = query.and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("api_key="))
+    });
+    bearer.or(header).or(param).map(str::trim)
+}
+
+/// Middleware for every route that needs a key. Does nothing unless
+/// `auth.keys_path` is set.
+pub async fn require_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(keys) = &state.keys else {
+        return next.run(request).await;
+    };
+    let client =
+        presented_key(request.headers(), request.uri().query()).and_then(|key| keys.client(key));
+    match client {
+        Some(name) => {
+            request.extensions_mut().insert(Client { name });
+            next.run(request).await
+        }
+        None => {
+            let err = ApiError::unauthorized("a valid API key is required");
+            ([(WWW_AUTHENTICATE, "Bearer")], err).into_response()
+        }
+    }
+}
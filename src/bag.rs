@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveTime, SecondsFormat, Utc};
+use rusqlite::{params_from_iter, types::Value};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{Database, DbError};
+use crate::detector::DetectionResult;
+use crate::regulations::{Regulations, Verdict};
+use crate::trips::TripContext;
+
+/// How many of a species the angler has already kept against its limits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BagStatus {
+    /// Kept since local midnight, on any trip.
+    pub kept_today: u32,
+    pub daily_limit: Option<u32>,
+    /// Kept on the current trip, which is what counts towards possession.
+    pub kept_this_trip: Option<u32>,
+    pub possession_limit: Option<u32>,
+}
+
+/// Start of the local day, as stored catch timestamps are written.
+fn start_of_today() -> String {
+    let midnight = Local::now().date_naive().and_time(NaiveTime::MIN);
+    midnight
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Stored species names normalized the way `SpeciesRule::names` is, so catches
+/// logged under any spelling or alias of a species count towards its rule.
+const NORMALIZED_SPECIES: &str = "replace(replace(lower(trim(species)), ' ', '_'), '-', '_')";
+
+/// Counts (today, this trip) keeps for the angler under each rule, keyed by
+/// the rule's class.
+async fn kept_counts(
+    db: &Database,
+    context: TripContext,
+    angler_id: i64,
+    rules: Vec<(String, Vec<String>)>,
+) -> Result<HashMap<String, (u32, Option<u32>)>, DbError> {
+    let since = start_of_today();
+    db.call(move |conn| {
+        let mut counts = HashMap::new();
+        for (class, names) in rules {
+            let placeholders = vec!["?"; names.len()].join(", ");
+            let count = |period: &str, bound: Value| -> rusqlite::Result<u32> {
+                let sql = format!(
+                    "SELECT COUNT(*) FROM catches WHERE angler_id = ? AND verdict = 'keep' \
+                     AND {} AND {} IN ({})",
+                    period, NORMALIZED_SPECIES, placeholders
+                );
+                let values = [Value::from(angler_id), bound]
+                    .into_iter()
+                    .chain(names.iter().cloned().map(Value::from));
+                conn.query_row(&sql, params_from_iter(values), |row| row.get(0))
+            };
+            let today = count("caught_at >= ?", since.clone().into())?;
+            let trip = match context.trip_id {
+                Some(trip_id) => Some(count("trip_id = ?", trip_id.into())?),
+                None => None,
+            };
+            counts.insert(class, (today, trip));
+        }
+        Ok(counts)
+    })
+    .await
+}
+
+/// Whether the angler may end up keeping the fish. An unmeasured fish still
+/// can't be kept once the limit is reached, but only a stored `keep` verdict
+/// counts towards it.
+fn might_keep(verdict: Verdict) -> bool {
+    matches!(verdict, Verdict::Keep | Verdict::CheckSize)
+}
+
+/// Adds bag status to every detection the regulations would let the angler keep,
+/// and turns the verdict into a release when one more keep would break the daily
+/// or possession limit. Does nothing without an angler.
+pub async fn apply_limits(
+    db: &Database,
+    regulations: &Regulations,
+    jurisdiction: &str,
+    context: TripContext,
+    detections: &mut [DetectionResult],
+) -> Result<(), DbError> {
+    let Some(angler_id) = context.angler_id else {
+        return Ok(());
+    };
+
+    let limited = |detection: &DetectionResult| {
+        let keep = detection
+            .assessment
+            .as_ref()
+            .is_some_and(|assessment| might_keep(assessment.verdict));
+        let rule = regulations.species(jurisdiction, detection.species());
+        keep && rule
+            .is_some_and(|rule| rule.daily_limit.is_some() || rule.possession_limit.is_some())
+    };
+    let mut rules: Vec<(String, Vec<String>)> = detections
+        .iter()
+        .filter(|detection| limited(detection))
+        .filter_map(|detection| regulations.species(jurisdiction, detection.species()))
+        .map(|rule| (rule.class.clone(), rule.names()))
+        .collect();
+    rules.sort();
+    rules.dedup();
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let counts = kept_counts(db, context, angler_id, rules).await?;
+    for detection in detections.iter_mut() {
+        let Some(rule) = regulations.species(jurisdiction, detection.species()) else {
+            continue;
+        };
+        let (Some(&(kept_today, kept_this_trip)), Some(assessment)) =
+            (counts.get(&rule.class), detection.assessment.as_mut())
+        else {
+            continue;
+        };
+        if !might_keep(assessment.verdict) {
+            continue;
+        }
+
+        if let Some(limit) = rule.daily_limit.filter(|limit| kept_today >= *limit) {
+            assessment.verdict = Verdict::Release;
+            assessment.rule = Some("daily_limit".to_string());
+            assessment.reason = format!(
+                "Daily limit of {} reached ({} kept today)",
+                limit, kept_today
+            );
+        } else if let Some(limit) = rule
+            .possession_limit
+            .filter(|limit| kept_this_trip.is_some_and(|kept| kept >= *limit))
+        {
+            assessment.verdict = Verdict::Release;
+            assessment.rule = Some("possession_limit".to_string());
+            assessment.reason = format!("Possession limit of {} reached", limit);
+        }
+        detection.bag = Some(BagStatus {
+            kept_today,
+            daily_limit: rule.daily_limit,
+            kept_this_trip,
+            possession_limit: rule.possession_limit,
+        });
+    }
+    Ok(())
+}
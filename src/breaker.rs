@@ -0,0 +1,101 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Requests flow normally.
+    Closed,
+    /// Too many consecutive failures; requests are refused until the cool-down ends.
+    Open,
+    /// The cool-down has ended and requests are let through to probe the service.
+    HalfOpen,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+}
+
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Stops calling a failing service after `failure_threshold` consecutive failures
+/// and tries again once `reset_after` has passed.
+pub struct CircuitBreaker {
+    inner: Mutex<Inner>,
+    failure_threshold: u32,
+    reset_after: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, reset_after: Duration) -> Self {
+        CircuitBreaker {
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+            failure_threshold,
+            reset_after,
+        }
+    }
+
+    /// The state as of now: an open breaker whose cool-down has ended is
+    /// half-open, whether or not a probe has been let through yet.
+    fn current(&self, inner: &Inner) -> BreakerState {
+        let cooling_down = inner
+            .opened_at
+            .is_none_or(|opened_at| opened_at.elapsed() < self.reset_after);
+        match inner.state {
+            BreakerState::Open if !cooling_down => BreakerState::HalfOpen,
+            state => state,
+        }
+    }
+
+    /// Whether a request may be sent right now.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = self.current(&inner);
+        inner.state != BreakerState::Open
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        if inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold
+        {
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Whether requests are being refused right now. Unlike `allow`, this doesn't
+    /// let a probe through once the cool-down has ended.
+    pub fn is_open(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        self.current(&inner) == BreakerState::Open
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let inner = self.inner.lock().unwrap();
+        BreakerStatus {
+            state: self.current(&inner),
+            consecutive_failures: inner.consecutive_failures,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{params, types::Type, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::api::ApiError;
+use crate::db::{Database, DbError};
+use crate::detector::DetectionResult;
+use crate::location::Location;
+use crate::pipeline;
+use crate::preprocess;
+use crate::regulations::Verdict;
+use crate::trips::{self, TripContext};
+use crate::AppState;
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1000;
+/// Recorded as the backend of catches posted with their own detection.
+const SUBMITTED_BACKEND: &str = "client";
+
+/// A catch the angler confirmed, as stored in the catch log.
+#[derive(Serialize, Debug, Clone)]
+pub struct Catch {
+    pub id: i64,
+    pub caught_at: DateTime<Utc>,
+    pub species: String,
+    pub confidence: f32,
+    pub length_cm: Option<f32>,
+    pub length_uncertainty_cm: Option<f32>,
+    pub verdict: Option<Verdict>,
+    /// Regulation rule behind the verdict, if one applied.
+    pub rule: Option<String>,
+    pub thumbnail_path: Option<String>,
+    pub backend: String,
+    pub angler_id: Option<i64>,
+    pub trip_id: Option<i64>,
+    pub location: Option<Location>,
+    /// Jurisdiction the verdict was given under.
+    pub jurisdiction: Option<String>,
+}
+
+/// Timestamps are stored as fixed-width RFC 3339 UTC text so they sort and
+/// compare correctly as strings.
+fn to_db_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<Catch> {
+    let caught_at: String = row.get("caught_at")?;
+    let caught_at = DateTime::parse_from_rfc3339(&caught_at)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(err)))?
+        .with_timezone(&Utc);
+    let verdict: Option<String> = row.get("verdict")?;
+    let latitude: Option<f64> = row.get("latitude")?;
+    let longitude: Option<f64> = row.get("longitude")?;
+    let location = match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => Some(Location {
+            latitude,
+            longitude,
+            accuracy_m: row.get("location_accuracy_m")?,
+        }),
+        _ => None,
+    };
+    Ok(Catch {
+        id: row.get("id")?,
+        caught_at,
+        species: row.get("species")?,
+        confidence: row.get("confidence")?,
+        length_cm: row.get("length_cm")?,
+        length_uncertainty_cm: row.get("length_uncertainty_cm")?,
+        verdict: verdict.as_deref().and_then(Verdict::parse),
+        rule: row.get("rule")?,
+        thumbnail_path: row.get("thumbnail_path")?,
+        backend: row.get("backend")?,
+        angler_id: row.get("angler_id")?,
+        trip_id: row.get("trip_id")?,
+        location,
+        jurisdiction: row.get("jurisdiction")?,
+    })
+}
+
+/// Stores a confirmed detection. `thumbnail` is an already-encoded JPEG, written
+/// to `image_dir` under the new catch's id. Keeps count towards the angler's bag
+/// limits.
+pub async fn record(
+    db: &Database,
+    image_dir: PathBuf,
+    detection: &DetectionResult,
+    context: TripContext,
+    location: Option<Location>,
+    thumbnail: Option<Vec<u8>>,
+    caught_at: DateTime<Utc>,
+) -> Result<Catch, DbError> {
+    let detection = detection.clone();
+    db.call(move |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO catches (caught_at, species, confidence, length_cm, \
+             length_uncertainty_cm, verdict, rule, backend, angler_id, trip_id, \
+             latitude, longitude, location_accuracy_m, jurisdiction) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                to_db_time(&caught_at),
+                detection.species(),
+                detection.confidence,
+                detection.length.map(|length| length.cm),
+                detection.length.map(|length| length.uncertainty_cm),
+                detection.assessment.as_ref().map(|a| a.verdict.as_str()),
+                detection.assessment.as_ref().and_then(|a| a.rule.clone()),
+                detection.backend,
+                context.angler_id,
+                context.trip_id,
+                location.map(|location| location.latitude),
+                location.map(|location| location.longitude),
+                location.and_then(|location| location.accuracy_m),
+                detection
+                    .assessment
+                    .as_ref()
+                    .map(|a| a.jurisdiction.clone()),
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+
+        if let Some(thumbnail) = thumbnail {
+            std::fs::create_dir_all(&image_dir)?;
+            let path = image_dir.join(format!("catch-{}.jpg", id));
+            std::fs::write(&path, thumbnail)?;
+            tx.execute(
+                "UPDATE catches SET thumbnail_path = ?1 WHERE id = ?2",
+                params![path.to_string_lossy().into_owned(), id],
+            )?;
+        }
+
+        let catch = tx.query_row("SELECT * FROM catches WHERE id = ?1", [id], from_row)?;
+        tx.commit()?;
+        Ok(catch)
+    })
+    .await
+}
+
+/// Which catches to return. Both bounds are optional; `to` is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct CatchFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub species: Option<String>,
+}
+
+/// Catches matching `filter`, newest first.
+pub async fn query(
+    db: &Database,
+    filter: CatchFilter,
+    limit: Option<u32>,
+) -> Result<Vec<Catch>, DbError> {
+    db.call(move |conn| {
+        let mut sql = "SELECT * FROM catches WHERE 1 = 1".to_string();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(from) = &filter.from {
+            sql.push_str(" AND caught_at >= ?");
+            values.push(to_db_time(from).into());
+        }
+        if let Some(to) = &filter.to {
+            sql.push_str(" AND caught_at < ?");
+            values.push(to_db_time(to).into());
+        }
+        if let Some(species) = filter.species {
+            sql.push_str(" AND species = ? COLLATE NOCASE");
+            values.push(species.into());
+        }
+        sql.push_str(" ORDER BY caught_at DESC, id DESC");
+        if let Some(limit) = limit {
+            sql.push_str(" LIMIT ?");
+            values.push(i64::from(limit).into());
+        }
+
+        let mut statement = conn.prepare(&sql)?;
+        let catches = statement
+            .query_map(rusqlite::params_from_iter(values), from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(catches)
+    })
+    .await
+}
+
+pub async fn find(db: &Database, id: i64) -> Result<Option<Catch>, DbError> {
+    db.call(move |conn| {
+        match conn.query_row("SELECT * FROM catches WHERE id = ?1", [id], from_row) {
+            Ok(catch) => Ok(Some(catch)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    })
+    .await
+}
+
+/// Parses a filter bound given either as an RFC 3339 timestamp or a UTC date.
+/// A date used as an upper bound includes the whole of that day.
+pub fn parse_bound(value: &str, upper: bool) -> Result<DateTime<Utc>, ApiError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        ApiError::bad_request(format!(
+            "invalid date '{}', expected YYYY-MM-DD or RFC 3339",
+            value
+        ))
+    })?;
+    let date = if upper {
+        date.succ_opt().unwrap_or(date)
+    } else {
+        date
+    };
+    Ok(date.and_time(chrono::NaiveTime::MIN).and_utc())
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    from: Option<String>,
+    to: Option<String>,
+    species: Option<String>,
+    limit: Option<u32>,
+}
+
+/// `GET /catches?from=&to=&species=&limit=`
+pub async fn list(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Vec<Catch>>, ApiError> {
+    let filter = CatchFilter {
+        from: params
+            .from
+            .as_deref()
+            .map(|v| parse_bound(v, false))
+            .transpose()?,
+        to: params
+            .to
+            .as_deref()
+            .map(|v| parse_bound(v, true))
+            .transpose()?,
+        species: params.species,
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    Ok(Json(query(&state.db, filter, Some(limit)).await?))
+}
+
+/// `GET /catches/:id`
+pub async fn get(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Catch>, ApiError> {
+    find(&state.db, id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("no catch with id {}", id)))
+}
+
+#[derive(Deserialize)]
+pub struct NewCatch {
+    /// The detection being confirmed, as returned by `/ws` or `/detect`.
+    detection: DetectionResult,
+    /// Base64 frame the detection came from, kept as a thumbnail.
+    image: Option<String>,
+    /// Defaults to now.
+    caught_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    context: TripContext,
+    /// Where it was caught; defaults to the GPS fix.
+    location: Option<Location>,
+}
+
+/// Turns a full frame into the thumbnail stored with a catch.
+pub async fn make_thumbnail(image: Vec<u8>, max_dimension: u32) -> Result<Vec<u8>, ApiError> {
+    tokio::task::spawn_blocking(move || preprocess::thumbnail(&image, max_dimension))
+        .await
+        .map_err(|err| ApiError::bad_request(err.to_string()))?
+        .map_err(|err| ApiError::bad_request(err.to_string()))
+}
+
+/// Logs a confirmed detection with a thumbnail of `image`, falling back to the
+/// GPS fix for the location, and tells subscribed WebSocket clients about it.
+/// The verdict is given afresh for where and when the fish was caught, since
+/// the bag may have filled up since the frame was analysed.
+pub async fn confirm(
+    state: &AppState,
+    detection: &DetectionResult,
+    context: TripContext,
+    location: Option<Location>,
+    image: Option<Vec<u8>>,
+    caught_at: DateTime<Utc>,
+) -> Result<Catch, ApiError> {
+    trips::check_context(&state.db, context).await?;
+    if let Some(location) = &location {
+        location.check().map_err(ApiError::bad_request)?;
+    }
+    let location = location.or_else(|| state.gps.as_ref().and_then(|gps| gps.current()));
+    let mut detection = detection.clone();
+    detection.assessment = None;
+    detection.bag = None;
+    let date = caught_at.with_timezone(&Local).date_naive();
+    pipeline::assess(
+        state,
+        std::slice::from_mut(&mut detection),
+        location.as_ref(),
+        context,
+        date,
+    )
+    .await;
+    let thumbnail = match image {
+        Some(image) => Some(make_thumbnail(image, state.config.storage.thumbnail_size).await?),
+        None => None,
+    };
+    let catch = record(
+        &state.db,
+        state.config.storage.image_dir.clone(),
+        &detection,
+        context,
+        location,
+        thumbnail,
+        caught_at,
+    )
+    .await?;
+    // Nobody listening is the usual case, not an error.
+    let _ = state.catch_events.send(catch.clone());
+    Ok(catch)
+}
+
+/// `POST /catches`: logs a detection the angler confirmed as a catch. Only
+/// the species, confidence and length are taken from the client; the verdict
+/// is the server's own.
+pub async fn create(
+    State(state): State<AppState>,
+    Json(mut new_catch): Json<NewCatch>,
+) -> Result<Json<Catch>, ApiError> {
+    // Nothing shows which detector, if any, produced a submitted detection.
+    new_catch.detection.backend = SUBMITTED_BACKEND.to_string();
+    let image = new_catch
+        .image
+        .map(|image| pipeline::decode_base64(&image))
+        .transpose()
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    let catch = confirm(
+        &state,
+        &new_catch.detection,
+        new_catch.context,
+        new_catch.location,
+        image,
+        new_catch.caught_at.unwrap_or_else(Utc::now),
+    )
+    .await?;
+    Ok(Json(catch))
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::SecondsFormat;
+
+use crate::auth::KeyFile;
+use crate::catches::{self, CatchFilter};
+use crate::config::Config;
+use crate::db::Database;
+use crate::export::{self, Format};
+
+pub const USAGE: &str = "\
+Usage:
+  ethicalfish-pi [serve]
+  ethicalfish-pi export [--format csv|geojson|jsonl] [--from DATE] [--to DATE]
+                        [--species NAME] [--output PATH]
+  ethicalfish-pi keys issue CLIENT | keys revoke ID | keys list
+
+Dates are YYYY-MM-DD (UTC, --to includes the whole day) or RFC 3339.
+Export writes to stdout unless --output is given.
+Keys are kept hashed in auth.keys_path; an issued key is printed only once.";
+
+pub enum Command {
+    Serve,
+    Help,
+    Export(ExportArgs),
+    Keys(KeysCommand),
+}
+
+pub enum KeysCommand {
+    Issue { client: String },
+    Revoke { id: String },
+    List,
+}
+
+pub struct ExportArgs {
+    format: Format,
+    filter: CatchFilter,
+    output: Option<PathBuf>,
+}
+
+fn parse_export(mut args: impl Iterator<Item = String>) -> Result<ExportArgs, String> {
+    let mut export = ExportArgs {
+        format: Format::Csv,
+        filter: CatchFilter::default(),
+        output: None,
+    };
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match flag.as_str() {
+            "--format" => export.format = value()?.parse()?,
+            "--from" => {
+                export.filter.from =
+                    Some(catches::parse_bound(&value()?, false).map_err(|err| err.message)?)
+            }
+            "--to" => {
+                export.filter.to =
+                    Some(catches::parse_bound(&value()?, true).map_err(|err| err.message)?)
+            }
+            "--species" => export.filter.species = Some(value()?),
+            "--output" | "-o" => export.output = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown export option '{}'", flag)),
+        }
+    }
+    Ok(export)
+}
+
+fn parse_keys(mut args: impl Iterator<Item = String>) -> Result<KeysCommand, String> {
+    let command = match (args.next().as_deref(), args.next()) {
+        (Some("issue"), Some(client)) => KeysCommand::Issue { client },
+        (Some("revoke"), Some(id)) => KeysCommand::Revoke { id },
+        (Some("list"), None) => KeysCommand::List,
+        _ => return Err("expected keys issue CLIENT, keys revoke ID or keys list".to_string()),
+    };
+    match args.next() {
+        Some(extra) => Err(format!("unexpected argument '{}'", extra)),
+        None => Ok(command),
+    }
+}
+
+/// Reads the subcommand from the process arguments, without the program name.
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    match args.next().as_deref() {
+        None | Some("serve") => Ok(Command::Serve),
+        Some("-h" | "--help" | "help") => Ok(Command::Help),
+        Some("export") => parse_export(args).map(Command::Export),
+        Some("keys") => parse_keys(args).map(Command::Keys),
+        Some(other) => Err(format!("unknown command '{}'", other)),
+    }
+}
+
+/// `export`: the same output as `GET /export`, for pulling reports off the
+/// device without running the server.
+pub async fn export(config: &Config, args: ExportArgs) -> Result<(), String> {
+    let db = Database::open(&config.storage.database_path)?;
+    let catches = export::load(&db, args.filter)
+        .await
+        .map_err(|err| err.to_string())?;
+    let body = export::render(args.format, &catches);
+    match &args.output {
+        Some(path) => std::fs::write(path, body)
+            .map_err(|err| format!("Failed to write {}: {}", path.display(), err))?,
+        None => std::io::stdout()
+            .write_all(body.as_bytes())
+            .map_err(|err| format!("Failed to write output: {}", err))?,
+    }
+    eprintln!(
+        "Exported {} catches as {} (schema version {}: {})",
+        catches.len(),
+        args.format,
+        export::SCHEMA_VERSION,
+        export::FIELDS.join(",")
+    );
+    Ok(())
+}
+
+/// `keys`: issues, revokes and lists API keys. A running server notices the
+/// change to the keys file on its own.
+pub fn keys(config: &Config, command: KeysCommand) -> Result<(), String> {
+    let path = config
+        .auth
+        .keys_path
+        .as_deref()
+        .ok_or("auth.keys_path (AUTH_KEYS_PATH) must be set to manage keys")?;
+    let mut file = KeyFile::load(path)?;
+    match command {
+        KeysCommand::Issue { client } => {
+            let (entry, key) = file.issue(&client)?;
+            file.save(path)?;
+            println!("{}", key);
+            eprintln!(
+                "Issued key {} for {}; it can't be shown again",
+                entry.id, entry.client
+            );
+        }
+        KeysCommand::Revoke { id } => {
+            let client = file.revoke(&id)?.client.clone();
+            file.save(path)?;
+            eprintln!("Revoked key {} for {}", id, client);
+        }
+        KeysCommand::List => {
+            for entry in &file.keys {
+                let state = match entry.revoked_at {
+                    Some(revoked_at) => format!(
+                        "revoked {}",
+                        revoked_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                    ),
+                    None => "active".to_string(),
+                };
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.id,
+                    entry.client,
+                    entry.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                    state
+                );
+            }
+        }
+    }
+    Ok(())
+}
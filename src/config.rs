@@ -0,0 +1,706 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Config file read when `ETHICALFISH_CONFIG` isn't set, if it exists.
+const DEFAULT_CONFIG_PATH: &str = "ethicalfish.toml";
+/// Retries beyond this only pile up waiting frames and Roboflow bills.
+const MAX_RETRIES: u32 = 10;
+
+/// Settings loaded once at startup.
+///
+/// Values come from, in increasing priority: built-in defaults, the TOML file,
+/// then the environment (including `.env`).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub detector: DetectorConfig,
+    pub roboflow: RoboflowConfig,
+    pub local: LocalConfig,
+    pub mock: MockConfig,
+    pub gaia: GaiaConfig,
+    pub preprocess: PreprocessConfig,
+    pub regulations: RegulationsConfig,
+    pub measurement: MeasurementConfig,
+    pub storage: StorageConfig,
+    pub gps: GpsConfig,
+    pub tracking: TrackingConfig,
+    pub filter: FilterConfig,
+    pub stream: StreamConfig,
+    pub logging: LoggingConfig,
+    pub auth: AuthConfig,
+    pub limits: LimitsConfig,
+    /// File the settings were read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Largest request body accepted by `POST /detect`.
+    pub max_upload_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            max_upload_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[default]
+    Roboflow,
+    Local,
+    Mock,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "roboflow" => Ok(Backend::Roboflow),
+            "local" => Ok(Backend::Local),
+            "mock" => Ok(Backend::Mock),
+            _ => Err("expected one of roboflow, local, mock".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectorConfig {
+    pub backend: Backend,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoboflowConfig {
+    pub api_key: Option<String>,
+    pub model_id: Option<String>,
+    pub model_version: Option<String>,
+    /// How long to spend on Roboflow per frame, retries included, before
+    /// falling back to the local model or giving up.
+    pub timeout_ms: u64,
+    pub connect_timeout_ms: u64,
+    /// Limit for a single HTTP attempt, including reading the response. Must be
+    /// less than `timeout_ms`.
+    pub request_timeout_ms: u64,
+    /// Extra attempts after a transient failure, at most 10.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after.
+    pub retry_backoff_ms: u64,
+    /// Consecutive failures that open the circuit breaker.
+    pub breaker_failure_threshold: u32,
+    /// How long the breaker stays open before letting a request through again.
+    pub breaker_reset_ms: u64,
+    /// Calls allowed per UTC day before frames go to the local model instead,
+    /// or are refused without one. Zero for no limit.
+    pub daily_quota: u64,
+    /// The same per calendar month.
+    pub monthly_quota: u64,
+}
+
+impl Default for RoboflowConfig {
+    fn default() -> Self {
+        RoboflowConfig {
+            api_key: None,
+            model_id: None,
+            model_version: None,
+            timeout_ms: 5000,
+            connect_timeout_ms: 1000,
+            // Two attempts and a backoff fit inside `timeout_ms`.
+            request_timeout_ms: 2000,
+            max_retries: 1,
+            retry_backoff_ms: 200,
+            breaker_failure_threshold: 5,
+            breaker_reset_ms: 30_000,
+            daily_quota: 0,
+            monthly_quota: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalConfig {
+    pub model_path: Option<PathBuf>,
+    pub labels_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MockConfig {
+    pub fixture_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GaiaConfig {
+    pub api_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreprocessConfig {
+    /// When off, frames go to the detector exactly as received.
+    pub enabled: bool,
+    /// Longest side, in pixels, of the image handed to the detector.
+    pub max_dimension: u32,
+    pub jpeg_quality: u8,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        PreprocessConfig {
+            enabled: true,
+            max_dimension: 1024,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegulationsConfig {
+    /// TOML file of per-jurisdiction rules. Detections aren't assessed without one.
+    pub path: Option<PathBuf>,
+    /// Jurisdiction id used to assess detections that aren't inside any zone.
+    pub jurisdiction: Option<String>,
+    /// GeoJSON polygons mapping locations to jurisdiction ids.
+    pub zones_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MeasurementConfig {
+    /// Detector class of the reference object. Lengths aren't estimated without one.
+    pub reference_class: Option<String>,
+    /// Real length of the reference object's long side.
+    pub reference_length_cm: f32,
+    /// Typical error of a box edge, in pixels of the frame the detector sees.
+    pub box_error_px: f32,
+    /// Typical relative error from a box not hugging the fish exactly.
+    pub fit_error: f32,
+}
+
+impl Default for MeasurementConfig {
+    fn default() -> Self {
+        MeasurementConfig {
+            reference_class: None,
+            reference_length_cm: 30.0,
+            box_error_px: 3.0,
+            fit_error: 0.05,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// SQLite database holding the catch log.
+    pub database_path: PathBuf,
+    /// Where catch thumbnails are written.
+    pub image_dir: PathBuf,
+    /// Longest side of a stored thumbnail, in pixels.
+    pub thumbnail_size: u32,
+    /// Below this much free space on the thumbnail disk the server reports
+    /// itself not ready.
+    pub min_free_disk_mb: u64,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            database_path: PathBuf::from("ethicalfish.db"),
+            image_dir: PathBuf::from("catches"),
+            thumbnail_size: 320,
+            min_free_disk_mb: 100,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpsConfig {
+    /// Serial device or file to read NMEA sentences from. No GPS without one.
+    pub device: Option<PathBuf>,
+    /// A fix older than this is treated as no fix.
+    pub max_fix_age_ms: u64,
+}
+
+impl Default for GpsConfig {
+    fn default() -> Self {
+        GpsConfig {
+            device: None,
+            max_fix_age_ms: 10_000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackingConfig {
+    /// Follow detections across WebSocket frames and smooth their classes.
+    pub enabled: bool,
+    /// Least box overlap for a detection to continue an existing track.
+    pub iou_threshold: f32,
+    /// Weight of the newest frame in each track's class scores.
+    pub smoothing: f32,
+    /// Frames a track survives without a matching detection.
+    pub max_missed_frames: u32,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        TrackingConfig {
+            enabled: true,
+            iou_threshold: 0.3,
+            smoothing: 0.3,
+            max_missed_frames: 5,
+        }
+    }
+}
+
+/// Which detections are passed on, whichever backend produced them. WebSocket
+/// clients can adjust these for their own connection.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Detections below this confidence are dropped.
+    pub min_confidence: f32,
+    /// Per-class minimums that replace `min_confidence` for those classes.
+    pub class_min_confidence: HashMap<String, f32>,
+    /// Most detections kept per frame, highest confidence first; 0 keeps all.
+    pub top_k: usize,
+    /// When not empty, only these classes are kept.
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    /// Of two boxes overlapping by more than this IoU, only the more confident
+    /// is kept; 1 turns suppression off.
+    pub nms_iou_threshold: f32,
+    /// Suppress overlapping boxes even when their classes differ.
+    pub class_agnostic_nms: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            min_confidence: 0.0,
+            class_min_confidence: HashMap::new(),
+            top_k: 0,
+            allow: Vec::new(),
+            deny: Vec::new(),
+            nms_iou_threshold: 0.5,
+            class_agnostic_nms: false,
+        }
+    }
+}
+
+impl FilterConfig {
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let thresholds = std::iter::once(("min_confidence".to_string(), self.min_confidence))
+            .chain(
+                self.class_min_confidence
+                    .iter()
+                    .map(|(class, min)| (format!("class_min_confidence.{}", class), *min)),
+            )
+            .chain(std::iter::once((
+                "nms_iou_threshold".to_string(),
+                self.nms_iou_threshold,
+            )));
+        for (name, value) in thresholds {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!("filter.{} must be between 0 and 1", name));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StreamConfig {
+    /// Frames from one WebSocket connection analysed at the same time. More
+    /// than one keeps results coming while Roboflow is slow, at the cost of
+    /// more API calls.
+    pub max_concurrent_inferences: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            max_concurrent_inferences: 1,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Multi-line, coloured output for someone watching a terminal.
+    #[default]
+    Pretty,
+    /// One JSON object per line, for a log collector.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("expected one of pretty, json".to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Filter in `RUST_LOG` syntax, such as `info` or `ethicalfish_pi=debug`.
+    /// `RUST_LOG` itself replaces it when set.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            format: LogFormat::default(),
+            level: "info".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Hashed API keys, managed with `ethicalfish-pi keys`. Once this is set,
+    /// every route but `/healthz` and `/readyz` needs a key.
+    pub keys_path: Option<PathBuf>,
+}
+
+/// Frame rate limits, checked before a frame is analysed.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Average frames per second each client may send; zero for no limit.
+    /// Clients are told apart by API key, or by address without keys.
+    pub client_frames_per_sec: f64,
+    /// Frames a client may send at once before the average applies.
+    pub client_burst: u32,
+    /// Average frames per second across all clients; zero for no limit.
+    pub global_frames_per_sec: f64,
+    pub global_burst: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            client_frames_per_sec: 0.0,
+            client_burst: 5,
+            global_frames_per_sec: 0.0,
+            global_burst: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Env { var: &'static str, reason: String },
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "Invalid {}: {}", path.display(), err),
+            ConfigError::Env { var, reason } => write!(f, "Invalid {}: {}", var, reason),
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads `.env`, the config file and the environment, then validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        let config = Config::load_for_cli()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Like `load`, but without the checks that only matter to a running
+    /// server, such as Roboflow credentials and model paths. The CLI commands
+    /// only need the database or the keys file, and check those themselves.
+    pub fn load_for_cli() -> Result<Self, ConfigError> {
+        // A missing .env is fine; the variables may come from the real environment.
+        dotenvy::dotenv().ok();
+
+        let source = match std::env::var("ETHICALFISH_CONFIG") {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Some(PathBuf::from(DEFAULT_CONFIG_PATH))
+            }
+            Err(_) => None,
+        };
+        let mut config = match &source {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.source = source;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| ConfigError::Read(path.into(), err))?;
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse(path.into(), err))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(bind) = parse_env("BIND_ADDRESS")? {
+            self.server.bind = bind;
+        }
+        if let Some(backend) = parse_env("DETECTOR_BACKEND")? {
+            self.detector.backend = backend;
+        }
+        override_env(&mut self.roboflow.api_key, "ROBOFLOW_API_KEY");
+        override_env(&mut self.roboflow.model_id, "ROBOFLOW_MODEL_ID");
+        override_env(&mut self.roboflow.model_version, "ROBOFLOW_MODEL_VERSION");
+        if let Some(timeout_ms) = parse_env("ROBOFLOW_TIMEOUT_MS")? {
+            self.roboflow.timeout_ms = timeout_ms;
+        }
+        override_env(&mut self.local.model_path, "LOCAL_MODEL_PATH");
+        override_env(&mut self.local.labels_path, "LOCAL_MODEL_LABELS");
+        override_env(&mut self.mock.fixture_path, "MOCK_FIXTURE_PATH");
+        override_env(&mut self.gaia.api_key, "GAIA_API_KEY");
+        override_env(&mut self.regulations.path, "REGULATIONS_PATH");
+        if let Ok(path) = std::env::var("DATABASE_PATH") {
+            self.storage.database_path = PathBuf::from(path);
+        }
+        override_env(
+            &mut self.regulations.jurisdiction,
+            "REGULATIONS_JURISDICTION",
+        );
+        override_env(&mut self.regulations.zones_path, "REGULATIONS_ZONES_PATH");
+        override_env(&mut self.gps.device, "GPS_DEVICE");
+        override_env(&mut self.auth.keys_path, "AUTH_KEYS_PATH");
+        if let Some(format) = parse_env("LOG_FORMAT")? {
+            self.logging.format = format;
+        }
+        Ok(())
+    }
+
+    /// Settings that are valid but probably don't do what was meant, to be
+    /// logged once logging is up.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let roboflow = &self.roboflow;
+        if self.detector.backend == Backend::Roboflow && roboflow.max_retries > 0 {
+            let attempts = u64::from(roboflow.max_retries) + 1;
+            let backoff = roboflow
+                .retry_backoff_ms
+                .saturating_mul((1 << roboflow.max_retries) - 1);
+            let worst = roboflow
+                .request_timeout_ms
+                .saturating_mul(attempts)
+                .saturating_add(backoff);
+            if worst > roboflow.timeout_ms {
+                warnings.push(format!(
+                    "roboflow retries can take up to {} ms, more than timeout_ms = {}; \
+                     lower max_retries = {} or request_timeout_ms",
+                    worst, roboflow.timeout_ms, roboflow.max_retries
+                ));
+            }
+        }
+        warnings
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.detector.backend == Backend::Roboflow {
+            for (value, name) in [
+                (
+                    &self.roboflow.api_key,
+                    "roboflow.api_key (ROBOFLOW_API_KEY)",
+                ),
+                (
+                    &self.roboflow.model_id,
+                    "roboflow.model_id (ROBOFLOW_MODEL_ID)",
+                ),
+                (
+                    &self.roboflow.model_version,
+                    "roboflow.model_version (ROBOFLOW_MODEL_VERSION)",
+                ),
+            ] {
+                if value.as_deref().unwrap_or("").is_empty() {
+                    problems.push(format!("{} is required for the roboflow backend", name));
+                }
+            }
+        }
+        for (value, name) in [
+            (self.roboflow.timeout_ms, "roboflow.timeout_ms"),
+            (
+                self.roboflow.connect_timeout_ms,
+                "roboflow.connect_timeout_ms",
+            ),
+            (
+                self.roboflow.request_timeout_ms,
+                "roboflow.request_timeout_ms",
+            ),
+            (
+                self.roboflow.breaker_failure_threshold.into(),
+                "roboflow.breaker_failure_threshold",
+            ),
+        ] {
+            if value == 0 {
+                problems.push(format!("{} must be greater than zero", name));
+            }
+        }
+        if self.roboflow.request_timeout_ms >= self.roboflow.timeout_ms {
+            problems.push(
+                "roboflow.request_timeout_ms must be less than roboflow.timeout_ms".to_string(),
+            );
+        }
+        if self.roboflow.max_retries > MAX_RETRIES {
+            problems.push(format!(
+                "roboflow.max_retries can't be more than {}",
+                MAX_RETRIES
+            ));
+        }
+        if self.preprocess.max_dimension == 0 {
+            problems.push("preprocess.max_dimension must be greater than zero".to_string());
+        }
+        if !(1..=100).contains(&self.preprocess.jpeg_quality) {
+            problems.push("preprocess.jpeg_quality must be between 1 and 100".to_string());
+        }
+        if self.detector.backend == Backend::Local && self.local.model_path.is_none() {
+            problems.push(
+                "local.model_path (LOCAL_MODEL_PATH) is required for the local backend".to_string(),
+            );
+        }
+        if self.measurement.reference_length_cm <= 0.0 {
+            problems.push("measurement.reference_length_cm must be greater than zero".to_string());
+        }
+        if self.measurement.box_error_px < 0.0 || self.measurement.fit_error < 0.0 {
+            problems.push("measurement error terms can't be negative".to_string());
+        }
+        if self.storage.thumbnail_size == 0 {
+            problems.push("storage.thumbnail_size must be greater than zero".to_string());
+        }
+        if self.regulations.path.is_some()
+            && self.regulations.jurisdiction.is_none()
+            && self.regulations.zones_path.is_none()
+        {
+            problems.push(
+                "regulations.jurisdiction (REGULATIONS_JURISDICTION) or regulations.zones_path is required with regulations.path"
+                    .to_string(),
+            );
+        }
+        if self.regulations.zones_path.is_some() && self.regulations.path.is_none() {
+            problems.push("regulations.zones_path needs regulations.path".to_string());
+        }
+        if !(self.tracking.iou_threshold > 0.0 && self.tracking.iou_threshold <= 1.0) {
+            problems.push("tracking.iou_threshold must be above 0 and at most 1".to_string());
+        }
+        if !(self.tracking.smoothing > 0.0 && self.tracking.smoothing <= 1.0) {
+            problems.push("tracking.smoothing must be above 0 and at most 1".to_string());
+        }
+        problems.extend(self.filter.problems());
+        for (rate, burst, name) in [
+            (
+                self.limits.client_frames_per_sec,
+                self.limits.client_burst,
+                "limits.client",
+            ),
+            (
+                self.limits.global_frames_per_sec,
+                self.limits.global_burst,
+                "limits.global",
+            ),
+        ] {
+            if rate < 0.0 || !rate.is_finite() {
+                problems.push(format!("{}_frames_per_sec must be zero or more", name));
+            } else if rate > 0.0 && burst == 0 {
+                problems.push(format!("{}_burst must be greater than zero", name));
+            }
+        }
+        if self.stream.max_concurrent_inferences == 0 {
+            problems.push("stream.max_concurrent_inferences must be greater than zero".to_string());
+        }
+        if self.gps.max_fix_age_ms == 0 {
+            problems.push("gps.max_fix_age_ms must be greater than zero".to_string());
+        }
+        for (path, name) in [
+            (&self.local.model_path, "local.model_path"),
+            (&self.local.labels_path, "local.labels_path"),
+            (&self.mock.fixture_path, "mock.fixture_path"),
+            (&self.regulations.path, "regulations.path"),
+            (&self.regulations.zones_path, "regulations.zones_path"),
+        ] {
+            if let Some(path) = path {
+                if !path.exists() {
+                    problems.push(format!("{} {} does not exist", name, path.display()));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+fn override_env<T: From<String>>(field: &mut Option<T>, var: &str) {
+    if let Ok(value) = std::env::var(var) {
+        *field = Some(T::from(value));
+    }
+}
+
+fn parse_env<T>(var: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match std::env::var(var) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|err: T::Err| ConfigError::Env {
+                var,
+                reason: format!("'{}': {}", value, err),
+            }),
+        Err(_) => Ok(None),
+    }
+}
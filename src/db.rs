@@ -0,0 +1,137 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+/// Schema changes, applied in order. `PRAGMA user_version` records how many have
+/// run, so only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE catches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        caught_at TEXT NOT NULL,
+        species TEXT NOT NULL,
+        confidence REAL NOT NULL,
+        length_cm REAL,
+        length_uncertainty_cm REAL,
+        verdict TEXT,
+        rule TEXT,
+        thumbnail_path TEXT,
+        backend TEXT NOT NULL
+    );
+    CREATE INDEX catches_caught_at ON catches (caught_at);
+    CREATE INDEX catches_species ON catches (species);
+",
+    "
+    CREATE TABLE anglers (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE trips (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT,
+        started_at TEXT NOT NULL,
+        ended_at TEXT
+    );
+    ALTER TABLE catches ADD COLUMN angler_id INTEGER REFERENCES anglers (id);
+    ALTER TABLE catches ADD COLUMN trip_id INTEGER REFERENCES trips (id);
+    CREATE INDEX catches_angler ON catches (angler_id, species, caught_at);
+    CREATE INDEX catches_trip ON catches (trip_id);
+",
+    "
+    ALTER TABLE catches ADD COLUMN latitude REAL;
+    ALTER TABLE catches ADD COLUMN longitude REAL;
+    ALTER TABLE catches ADD COLUMN location_accuracy_m REAL;
+    ALTER TABLE catches ADD COLUMN jurisdiction TEXT;
+    CREATE INDEX catches_jurisdiction ON catches (jurisdiction);
+",
+    "
+    CREATE TABLE roboflow_usage (
+        period TEXT PRIMARY KEY,
+        calls INTEGER NOT NULL
+    );
+",
+];
+
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    Io(std::io::Error),
+    Task(String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Sqlite(err) => write!(f, "Database error: {}", err),
+            DbError::Io(err) => write!(f, "Storage error: {}", err),
+            DbError::Task(err) => write!(f, "Database task failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(err: rusqlite::Error) -> Self {
+        DbError::Sqlite(err)
+    }
+}
+
+impl From<std::io::Error> for DbError {
+    fn from(err: std::io::Error) -> Self {
+        DbError::Io(err)
+    }
+}
+
+/// The local SQLite database. Queries run on the blocking pool so they never
+/// stall the WebSocket tasks.
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    /// Opens (creating if needed) the database at `path` and brings its schema
+    /// up to date.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut conn = Connection::open(path)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+        migrate(&mut conn)
+            .map_err(|err| format!("Failed to migrate {}: {}", path.display(), err))?;
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn call<F, T>(&self, f: F) -> Result<T, DbError>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, DbError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| DbError::Task("connection lock poisoned".to_string()))?;
+            f(&mut conn)
+        })
+        .await
+        .map_err(|err| DbError::Task(err.to_string()))?
+    }
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let applied: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", version as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
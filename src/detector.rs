@@ -0,0 +1,295 @@
+mod fallback;
+mod local;
+mod metered;
+mod mock;
+mod roboflow;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::bag::BagStatus;
+use crate::breaker::CircuitBreaker;
+use crate::config::{Backend, Config};
+use crate::measure::Length;
+use crate::metrics::Metrics;
+use crate::quota::Quota;
+use crate::regulations::Assessment;
+use crate::tracker::TrackInfo;
+
+pub use fallback::FallbackDetector;
+pub use local::LocalDetector;
+pub use metered::MeteredDetector;
+pub use mock::MockDetector;
+pub use roboflow::RoboflowDetector;
+
+/// Axis-aligned rectangle; `x` and `y` are the top-left corner.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn scaled(&self, factor: f32) -> Rect {
+        Rect {
+            x: self.x * factor,
+            y: self.y * factor,
+            width: self.width * factor,
+            height: self.height * factor,
+        }
+    }
+
+    /// Intersection over union; 0 for disjoint or empty rectangles.
+    pub fn iou(&self, other: &Rect) -> f32 {
+        let width = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let height = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        if width <= 0.0 || height <= 0.0 {
+            return 0.0;
+        }
+        let intersection = width * height;
+        let union = self.width * self.height + other.width * other.height - intersection;
+        if union <= 0.0 {
+            0.0
+        } else {
+            intersection / union
+        }
+    }
+}
+
+/// A detection's box, both in source pixels and as fractions of the image size.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub pixel: Rect,
+    pub normalized: Rect,
+}
+
+impl BoundingBox {
+    /// Builds a box from a center point and size in pixels, as Roboflow reports them.
+    pub fn from_center(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        image_width: u32,
+        image_height: u32,
+    ) -> Self {
+        let pixel = Rect {
+            x: x - width / 2.0,
+            y: y - height / 2.0,
+            width,
+            height,
+        };
+        let (iw, ih) = (image_width.max(1) as f32, image_height.max(1) as f32);
+        BoundingBox {
+            pixel,
+            normalized: Rect {
+                x: pixel.x / iw,
+                y: pixel.y / ih,
+                width: pixel.width / iw,
+                height: pixel.height / ih,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DetectionResult {
+    /// Position of this detection within the frame's results.
+    #[serde(default)]
+    pub index: usize,
+    pub class: String,
+    #[serde(default)]
+    pub class_id: Option<u32>,
+    pub confidence: f32,
+    /// Absent for whole-frame classifiers such as the local ResNet.
+    #[serde(rename = "box", default)]
+    pub bounding_box: Option<BoundingBox>,
+    /// Name of the detector that produced this result.
+    #[serde(default)]
+    pub backend: String,
+    /// Estimated from a reference object in the same frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<Length>,
+    /// Keep/release decision, when regulations are configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assessment: Option<Assessment>,
+    /// Progress towards bag limits, when the frame is tied to an angler.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bag: Option<BagStatus>,
+    /// Identity across frames on the same WebSocket connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<TrackInfo>,
+}
+
+impl DetectionResult {
+    /// The species to act on: the track's smoothed class when there is one, so
+    /// a single noisy frame can't flip the verdict.
+    pub fn species(&self) -> &str {
+        self.track
+            .as_ref()
+            .map_or(self.class.as_str(), |track| track.class.as_str())
+    }
+}
+
+/// Why a frame couldn't be turned into detections. Each variant maps to a stable
+/// `code` that clients can switch on.
+#[derive(Debug)]
+pub enum DetectorError {
+    /// The frame couldn't be decoded as an image.
+    InvalidImage(String),
+    /// A message or field alongside the frame was malformed.
+    InvalidRequest(String),
+    /// Roboflow rejected the API key.
+    Unauthorized(String),
+    /// The Roboflow account is out of credits or being rate limited.
+    QuotaExceeded(String),
+    /// Roboflow answered with some other non-success status.
+    Upstream { status: u16, message: String },
+    /// Roboflow couldn't be reached.
+    Network(reqwest::Error),
+    /// The backend didn't answer in time.
+    Timeout,
+    /// Roboflow has failed repeatedly and is being left alone for a while.
+    CircuitOpen,
+    /// The client, or everyone together, is sending frames too fast; retry
+    /// after the given wait.
+    RateLimited(Duration),
+    /// Roboflow answered with something we couldn't parse.
+    Parse(String),
+    /// The local model failed to load or run.
+    Model(String),
+}
+
+impl DetectorError {
+    /// Machine-readable error code sent to clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DetectorError::InvalidImage(_) => "invalid_image",
+            DetectorError::InvalidRequest(_) => "invalid_request",
+            DetectorError::Unauthorized(_) => "unauthorized",
+            DetectorError::QuotaExceeded(_) => "quota_exceeded",
+            DetectorError::Upstream { .. } => "upstream_error",
+            DetectorError::Network(_) => "network_error",
+            DetectorError::Timeout => "timeout",
+            DetectorError::CircuitOpen => "circuit_open",
+            DetectorError::RateLimited(_) => "rate_limited",
+            DetectorError::Parse(_) => "invalid_response",
+            DetectorError::Model(_) => "model_unavailable",
+        }
+    }
+
+    /// Whether the failure is down to the network or the upstream service rather
+    /// than the request itself, so another backend might still succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            DetectorError::Network(err) => err.is_connect() || err.is_timeout(),
+            DetectorError::Timeout | DetectorError::CircuitOpen => true,
+            DetectorError::Upstream { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DetectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectorError::InvalidImage(err) => write!(f, "Invalid image: {}", err),
+            DetectorError::InvalidRequest(err) => write!(f, "Invalid request: {}", err),
+            DetectorError::Unauthorized(err) => write!(f, "Roboflow rejected the API key: {}", err),
+            DetectorError::QuotaExceeded(err) => write!(f, "API quota exceeded: {}", err),
+            DetectorError::Upstream { status, message } => {
+                write!(f, "Roboflow API error ({}): {}", status, message)
+            }
+            DetectorError::Network(err) => write!(f, "Failed to reach Roboflow: {}", err),
+            DetectorError::Timeout => write!(f, "Detector timed out"),
+            DetectorError::CircuitOpen => {
+                write!(f, "Roboflow is failing repeatedly; requests are paused")
+            }
+            DetectorError::RateLimited(wait) => write!(
+                f,
+                "Too many frames; try again in {:.1}s",
+                wait.as_secs_f64()
+            ),
+            DetectorError::Parse(err) => write!(f, "Failed to parse Roboflow response: {}", err),
+            DetectorError::Model(err) => write!(f, "Model unavailable: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DetectorError {}
+
+/// A backend that turns an encoded image into detections.
+///
+/// The WebSocket handler only ever talks to a `dyn Detector`, so backends can be
+/// swapped at startup without touching the socket code.
+#[async_trait]
+pub trait Detector: Send + Sync {
+    /// Short backend name, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Whether a frame sent now could be answered, and if not, why.
+    fn ready(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn detect(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError>;
+}
+
+fn load_local(config: &Config) -> Result<Option<LocalDetector>, String> {
+    let Some(model_path) = &config.local.model_path else {
+        return Ok(None);
+    };
+    LocalDetector::load(model_path, config.local.labels_path.as_deref()).map(Some)
+}
+
+/// Builds the detector selected by `detector.backend`.
+///
+/// When Roboflow is selected and a local model is configured, the local model is
+/// used whenever Roboflow is unreachable, slower than `roboflow.timeout_ms` or
+/// out of quota.
+pub fn from_config(
+    config: &Config,
+    client: &reqwest::Client,
+    breaker: &Arc<CircuitBreaker>,
+    metrics: &Arc<Metrics>,
+    quota: &Arc<Quota>,
+) -> Result<Arc<dyn Detector>, String> {
+    let metered = |detector: Arc<dyn Detector>| -> Arc<dyn Detector> {
+        Arc::new(MeteredDetector::new(detector, metrics.clone()))
+    };
+    match config.detector.backend {
+        Backend::Roboflow => {
+            let roboflow = metered(Arc::new(RoboflowDetector::new(
+                &config.roboflow,
+                client.clone(),
+                breaker.clone(),
+                metrics.clone(),
+                quota.clone(),
+            )));
+            let Some(local) = load_local(config)? else {
+                return Ok(roboflow);
+            };
+            Ok(Arc::new(FallbackDetector::new(
+                roboflow,
+                metered(Arc::new(local)),
+            )))
+        }
+        Backend::Local => match load_local(config)? {
+            Some(local) => Ok(metered(Arc::new(local))),
+            None => Err("local.model_path must be set for the local backend".to_string()),
+        },
+        Backend::Mock => {
+            let detector = match &config.mock.fixture_path {
+                Some(path) => MockDetector::from_fixture(path)?,
+                None => MockDetector::default(),
+            };
+            Ok(metered(Arc::new(detector)))
+        }
+    }
+}
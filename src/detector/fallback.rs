@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use super::{DetectionResult, Detector, DetectorError};
+
+/// Tries the primary detector first and transparently runs the fallback when the
+/// primary times out, runs out of quota or fails for a reason that isn't the
+/// image's fault. The primary enforces its own timeout, so that giving up on it
+/// is recorded like any other failure.
+pub struct FallbackDetector {
+    primary: Arc<dyn Detector>,
+    fallback: Arc<dyn Detector>,
+}
+
+impl FallbackDetector {
+    pub fn new(primary: Arc<dyn Detector>, fallback: Arc<dyn Detector>) -> Self {
+        FallbackDetector { primary, fallback }
+    }
+}
+
+#[async_trait]
+impl Detector for FallbackDetector {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    fn ready(&self) -> Result<(), String> {
+        self.primary.ready().or_else(|primary| {
+            self.fallback.ready().map_err(|fallback| {
+                format!(
+                    "{}: {}; {}: {}",
+                    self.primary.name(),
+                    primary,
+                    self.fallback.name(),
+                    fallback
+                )
+            })
+        })
+    }
+
+    async fn detect(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        match self.primary.detect(image).await {
+            Ok(results) => return Ok(results),
+            // Running out of quota is the primary's problem, not the frame's.
+            Err(err) if !err.is_transient() && !matches!(err, DetectorError::QuotaExceeded(_)) => {
+                return Err(err)
+            }
+            Err(err) => warn!(
+                primary = self.primary.name(),
+                fallback = self.fallback.name(),
+                error = %err,
+                "Detector failed, falling back"
+            ),
+        }
+        self.fallback.detect(image).await
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tch::{
+    nn::{self, ModuleT},
+    vision::{imagenet, resnet},
+    CModule, Device, Kind,
+};
+
+use super::{DetectionResult, Detector, DetectorError};
+
+// How many of the highest scoring classes are reported per frame.
+const TOP_K: i64 = 5;
+
+enum Network {
+    TorchScript(CModule),
+    // The VarStore owns the weights the ResNet closure points at, so it has to
+    // live as long as the network does.
+    VarStore {
+        _vs: nn::VarStore,
+        net: nn::FuncT<'static>,
+    },
+}
+
+struct LocalModel {
+    network: Mutex<Network>,
+    labels: Vec<String>,
+    device: Device,
+}
+
+/// ResNet classifier that runs on the Pi itself, for when there's no signal.
+pub struct LocalDetector {
+    model: Arc<LocalModel>,
+}
+
+impl LocalDetector {
+    /// Loads a checkpoint from `model_path`.
+    ///
+    /// `.ot` files are treated as `nn::VarStore` weights for a ResNet-18 with one
+    /// output per label, anything else as a TorchScript module. Without a labels
+    /// file the ImageNet class names are used.
+    pub fn load(model_path: &Path, labels_path: Option<&Path>) -> Result<Self, String> {
+        let device = Device::cuda_if_available();
+
+        let labels: Vec<String> = match labels_path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|err| format!("Failed to read labels {}: {}", path.display(), err))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect(),
+            None => imagenet::CLASSES.iter().map(|c| c.to_string()).collect(),
+        };
+        if labels.is_empty() {
+            return Err("Labels file contains no classes".to_string());
+        }
+
+        let network = if model_path.extension().is_some_and(|ext| ext == "ot") {
+            let mut vs = nn::VarStore::new(device);
+            let net = resnet::resnet18(&vs.root(), labels.len() as i64);
+            vs.load(model_path).map_err(|err| {
+                format!("Failed to load weights {}: {}", model_path.display(), err)
+            })?;
+            Network::VarStore { _vs: vs, net }
+        } else {
+            let mut module = CModule::load_on_device(model_path, device).map_err(|err| {
+                format!(
+                    "Failed to load TorchScript {}: {}",
+                    model_path.display(),
+                    err
+                )
+            })?;
+            module.set_eval();
+            Network::TorchScript(module)
+        };
+
+        Ok(LocalDetector {
+            model: Arc::new(LocalModel {
+                network: Mutex::new(network),
+                labels,
+                device,
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl Detector for LocalDetector {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn detect(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        let model = self.model.clone();
+        let image = image.to_vec();
+        tokio::task::spawn_blocking(move || model.classify(&image))
+            .await
+            .map_err(|err| DetectorError::Model(format!("Local inference task failed: {}", err)))?
+    }
+}
+
+impl LocalModel {
+    // Blocks for the duration of the forward pass.
+    fn classify(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        let input = imagenet::load_image_and_resize224_from_memory(image)
+            .map_err(|err| DetectorError::InvalidImage(err.to_string()))?
+            .unsqueeze(0)
+            .to_device(self.device);
+
+        let network = self
+            .network
+            .lock()
+            .map_err(|_| DetectorError::Model("Local model lock poisoned".to_string()))?;
+        let logits = tch::no_grad(|| match &*network {
+            Network::TorchScript(module) => module.forward_ts(&[input]),
+            Network::VarStore { net, .. } => Ok(net.forward_t(&input, false)),
+        })
+        .map_err(|err| DetectorError::Model(format!("Local inference failed: {}", err)))?;
+
+        let probabilities = logits
+            .softmax(-1, Kind::Float)
+            .squeeze_dim(0)
+            .to_device(Device::Cpu);
+        let k = TOP_K.min(self.labels.len() as i64);
+        let (values, indices) = probabilities.topk(k, -1, true, true);
+
+        Ok((0..k)
+            .map(|i| {
+                let class_id = indices.int64_value(&[i]) as usize;
+                DetectionResult {
+                    index: i as usize,
+                    class: self
+                        .labels
+                        .get(class_id)
+                        .cloned()
+                        .unwrap_or_else(|| class_id.to_string()),
+                    class_id: Some(class_id as u32),
+                    confidence: values.double_value(&[i]) as f32,
+                    bounding_box: None,
+                    backend: "local".to_string(),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+
+use super::{DetectionResult, Detector, DetectorError};
+use crate::metrics::Metrics;
+
+/// Times every call to a backend for the inference latency histogram. Wraps
+/// each backend on its own, so a fallback is timed under its own name.
+pub struct MeteredDetector {
+    inner: Arc<dyn Detector>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredDetector {
+    pub fn new(inner: Arc<dyn Detector>, metrics: Arc<Metrics>) -> Self {
+        MeteredDetector { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl Detector for MeteredDetector {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn ready(&self) -> Result<(), String> {
+        self.inner.ready()
+    }
+
+    async fn detect(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        let started = Instant::now();
+        let result = self.inner.detect(image).await;
+        self.metrics.inference(self.inner.name(), started.elapsed());
+        result
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use super::{BoundingBox, DetectionResult, Detector, DetectorError};
+
+/// Returns the same canned detections for every frame, so the server can be
+/// exercised without a network or a model checkpoint.
+pub struct MockDetector {
+    detections: Vec<DetectionResult>,
+}
+
+impl MockDetector {
+    /// Loads the detections from a JSON fixture holding an array of results.
+    pub fn from_fixture(path: &Path) -> Result<Self, String> {
+        let fixture = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read fixture {}: {}", path.display(), err))?;
+        let mut detections: Vec<DetectionResult> = serde_json::from_str(&fixture)
+            .map_err(|err| format!("Invalid fixture {}: {}", path.display(), err))?;
+        for (index, detection) in detections.iter_mut().enumerate() {
+            detection.index = index;
+            detection.backend = "mock".to_string();
+        }
+        Ok(MockDetector { detections })
+    }
+}
+
+impl Default for MockDetector {
+    fn default() -> Self {
+        MockDetector {
+            detections: vec![DetectionResult {
+                index: 0,
+                class: "rainbow_trout".to_string(),
+                class_id: Some(0),
+                confidence: 0.9,
+                bounding_box: Some(BoundingBox::from_center(
+                    320.0, 240.0, 400.0, 160.0, 640, 480,
+                )),
+                backend: "mock".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+}
+
+#[async_trait]
+impl Detector for MockDetector {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn detect(&self, _image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        Ok(self.detections.clone())
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::time::Instant;
+use tracing::warn;
+
+use super::{BoundingBox, DetectionResult, Detector, DetectorError};
+use crate::breaker::CircuitBreaker;
+use crate::config::RoboflowConfig;
+use crate::metrics::Metrics;
+use crate::quota::Quota;
+
+/// Longest wait between two attempts, however many retries are configured.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug)]
+struct RoboflowPrediction {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    confidence: f32,
+    class: String,
+    class_id: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct RoboflowResponse {
+    predictions: Vec<RoboflowPrediction>,
+}
+
+/// Hosted inference through the Roboflow detect API.
+pub struct RoboflowDetector {
+    url: String,
+    client: Client,
+    breaker: Arc<CircuitBreaker>,
+    metrics: Arc<Metrics>,
+    quota: Arc<Quota>,
+    max_retries: u32,
+    retry_backoff: Duration,
+    /// Budget for a whole frame, retries and backoff included.
+    timeout: Duration,
+}
+
+impl RoboflowDetector {
+    /// Expects a validated config, so missing settings end up as empty strings.
+    /// `client` is shared with the rest of the app so connections are pooled.
+    pub fn new(
+        config: &RoboflowConfig,
+        client: Client,
+        breaker: Arc<CircuitBreaker>,
+        metrics: Arc<Metrics>,
+        quota: Arc<Quota>,
+    ) -> Self {
+        RoboflowDetector {
+            url: format!(
+                "https://detect.roboflow.com/{}/{}?api_key={}",
+                config.model_id.as_deref().unwrap_or_default(),
+                config.model_version.as_deref().unwrap_or_default(),
+                config.api_key.as_deref().unwrap_or_default()
+            ),
+            client,
+            breaker,
+            metrics,
+            quota,
+            max_retries: config.max_retries,
+            retry_backoff: Duration::from_millis(config.retry_backoff_ms),
+            timeout: Duration::from_millis(config.timeout_ms),
+        }
+    }
+
+    async fn request(&self, body: String) -> Result<RoboflowResponse, DetectorError> {
+        let resp = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_timeout() {
+                    self.metrics.roboflow_error("timeout");
+                    DetectorError::Timeout
+                } else {
+                    self.metrics.roboflow_error("network");
+                    // The URL carries the API key, and this error is shown to
+                    // clients as well as logged.
+                    DetectorError::Network(err.without_url())
+                }
+            })?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            self.metrics.roboflow_error(status.as_str());
+            let message = resp
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    DetectorError::Unauthorized(message)
+                }
+                StatusCode::PAYMENT_REQUIRED | StatusCode::TOO_MANY_REQUESTS => {
+                    DetectorError::QuotaExceeded(message)
+                }
+                _ => DetectorError::Upstream {
+                    status: status.as_u16(),
+                    message,
+                },
+            });
+        }
+
+        resp.json::<RoboflowResponse>().await.map_err(|err| {
+            self.metrics.roboflow_error("parse");
+            DetectorError::Parse(err.to_string())
+        })
+    }
+}
+
+#[async_trait]
+impl Detector for RoboflowDetector {
+    fn name(&self) -> &'static str {
+        "roboflow"
+    }
+
+    fn ready(&self) -> Result<(), String> {
+        if self.breaker.is_open() {
+            return Err("circuit breaker is open".to_string());
+        }
+        match self.quota.exhausted() {
+            Some(reason) => Err(reason),
+            None => Ok(()),
+        }
+    }
+
+    async fn detect(&self, image: &[u8]) -> Result<Vec<DetectionResult>, DetectorError> {
+        // Roboflow reports boxes in source pixels; the dimensions are needed to
+        // normalize them.
+        let (image_width, image_height) = image::io::Reader::new(Cursor::new(image))
+            .with_guessed_format()
+            .map_err(|err| DetectorError::InvalidImage(err.to_string()))?
+            .into_dimensions()
+            .map_err(|err| DetectorError::InvalidImage(err.to_string()))?;
+
+        let body = format!("image={}", STANDARD.encode(image));
+        // Enforced here rather than by the caller, so running out of time
+        // still counts as a failure towards the breaker and the metrics.
+        let deadline = Instant::now() + self.timeout;
+        let mut attempt = 0;
+        let json_response = loop {
+            if !self.breaker.allow() {
+                return Err(DetectorError::CircuitOpen);
+            }
+            // Every attempt is billed, retries included.
+            self.quota.spend().await?;
+            let result = match tokio::time::timeout_at(deadline, self.request(body.clone())).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.metrics.roboflow_error("timeout");
+                    Err(DetectorError::Timeout)
+                }
+            };
+            match result {
+                Ok(json_response) => {
+                    self.breaker.record_success();
+                    break json_response;
+                }
+                Err(err) => {
+                    self.breaker.record_failure();
+                    if !err.is_transient() || attempt >= self.max_retries {
+                        return Err(err);
+                    }
+                    let backoff = self
+                        .retry_backoff
+                        .saturating_mul(2u32.saturating_pow(attempt))
+                        .min(MAX_RETRY_BACKOFF);
+                    // No point waiting for an attempt that couldn't finish in time.
+                    if Instant::now() + backoff >= deadline {
+                        return Err(err);
+                    }
+                    warn!(
+                        attempt = attempt + 1,
+                        ?backoff,
+                        error = %err,
+                        "Roboflow request failed, retrying"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+            }
+        };
+
+        Ok(json_response
+            .predictions
+            .into_iter()
+            .enumerate()
+            .map(|(index, p)| DetectionResult {
+                index,
+                class: p.class,
+                class_id: Some(p.class_id),
+                confidence: p.confidence,
+                bounding_box: Some(BoundingBox::from_center(
+                    p.x,
+                    p.y,
+                    p.width,
+                    p.height,
+                    image_width,
+                    image_height,
+                )),
+                backend: self.name().to_string(),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+use std::str::FromStr;
+
+use axum::{
+    extract::{Query, State},
+    http::header::{HeaderName, CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::ApiError;
+use crate::catches::{self, Catch, CatchFilter};
+use crate::db::{Database, DbError};
+use crate::AppState;
+
+/// Bumped whenever a field is renamed, removed or changes meaning. New fields
+/// are only ever added at the end.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Export fields in order: the CSV header, the JSON Lines keys and the GeoJSON
+/// properties plus `latitude`/`longitude`, which GeoJSON carries as the geometry.
+pub const FIELDS: &[&str] = &[
+    "id",
+    "caught_at",
+    "species",
+    "confidence",
+    "length_cm",
+    "length_uncertainty_cm",
+    "verdict",
+    "rule",
+    "jurisdiction",
+    "latitude",
+    "longitude",
+    "location_accuracy_m",
+    "angler_id",
+    "trip_id",
+    "backend",
+    "thumbnail_path",
+];
+
+const SCHEMA_VERSION_HEADER: HeaderName = HeaderName::from_static("x-export-schema-version");
+const FIELDS_HEADER: HeaderName = HeaderName::from_static("x-export-fields");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    GeoJson,
+    Jsonl,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "geojson" => Ok(Format::GeoJson),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!(
+                "unknown format '{}', expected csv, geojson or jsonl",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Csv => "csv",
+            Format::GeoJson => "geojson",
+            Format::Jsonl => "jsonl",
+        })
+    }
+}
+
+impl Format {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::GeoJson => "application/geo+json",
+            Format::Jsonl => "application/x-ndjson",
+        }
+    }
+}
+
+/// The f64 closest to how `value` prints, so `0.9` stays `0.9` rather than
+/// becoming `0.8999999761581421`.
+fn widen(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value.into())
+}
+
+/// One exported catch, flattened so every format shares the same field names.
+#[derive(Serialize)]
+struct Record<'a> {
+    id: i64,
+    caught_at: String,
+    species: &'a str,
+    // f64 throughout, so CSV and GeoJSON, which go through `serde_json::Value`,
+    // print the same numbers as JSON Lines.
+    confidence: f64,
+    length_cm: Option<f64>,
+    length_uncertainty_cm: Option<f64>,
+    verdict: Option<&'static str>,
+    rule: Option<&'a str>,
+    jurisdiction: Option<&'a str>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    location_accuracy_m: Option<f64>,
+    angler_id: Option<i64>,
+    trip_id: Option<i64>,
+    backend: &'a str,
+    thumbnail_path: Option<&'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn new(catch: &'a Catch) -> Self {
+        Record {
+            id: catch.id,
+            caught_at: catch.caught_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            species: &catch.species,
+            confidence: widen(catch.confidence),
+            length_cm: catch.length_cm.map(widen),
+            length_uncertainty_cm: catch.length_uncertainty_cm.map(widen),
+            verdict: catch.verdict.map(|verdict| verdict.as_str()),
+            rule: catch.rule.as_deref(),
+            jurisdiction: catch.jurisdiction.as_deref(),
+            latitude: catch.location.map(|location| location.latitude),
+            longitude: catch.location.map(|location| location.longitude),
+            location_accuracy_m: catch
+                .location
+                .and_then(|location| location.accuracy_m)
+                .map(widen),
+            angler_id: catch.angler_id,
+            trip_id: catch.trip_id,
+            backend: &catch.backend,
+            thumbnail_path: catch.thumbnail_path.as_deref(),
+        }
+    }
+
+    fn to_map(&self) -> serde_json::Map<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+/// Quotes a CSV cell when it holds a delimiter, quote or line break (RFC 4180).
+fn csv_cell(value: Option<&Value>) -> String {
+    let text = match value {
+        None | Some(Value::Null) => return String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn csv_line(cells: impl Iterator<Item = String>) -> String {
+    let mut line = cells.collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Writes `catches` out in `format`.
+pub fn render(format: Format, catches: &[Catch]) -> String {
+    let records = catches.iter().map(Record::new);
+    match format {
+        Format::Csv => {
+            let mut out = csv_line(FIELDS.iter().map(|field| field.to_string()));
+            for record in records {
+                let map = record.to_map();
+                out.push_str(&csv_line(
+                    FIELDS.iter().map(|field| csv_cell(map.get(*field))),
+                ));
+            }
+            out
+        }
+        Format::Jsonl => {
+            let mut out = String::new();
+            for record in records {
+                out.push_str(&serde_json::to_string(&record).unwrap_or_default());
+                out.push('\n');
+            }
+            out
+        }
+        Format::GeoJson => {
+            let features: Vec<Value> = records
+                .map(|record| {
+                    let geometry = match (record.longitude, record.latitude) {
+                        (Some(longitude), Some(latitude)) => {
+                            json!({ "type": "Point", "coordinates": [longitude, latitude] })
+                        }
+                        _ => Value::Null,
+                    };
+                    let mut properties = record.to_map();
+                    properties.remove("latitude");
+                    properties.remove("longitude");
+                    json!({
+                        "type": "Feature",
+                        "id": record.id,
+                        "geometry": geometry,
+                        "properties": properties,
+                    })
+                })
+                .collect();
+            json!({ "type": "FeatureCollection", "features": features }).to_string()
+        }
+    }
+}
+
+/// Catches in the filter, oldest first as reports read them.
+pub async fn load(db: &Database, filter: CatchFilter) -> Result<Vec<Catch>, DbError> {
+    let mut catches = catches::query(db, filter, None).await?;
+    catches.reverse();
+    Ok(catches)
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    format: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    species: Option<String>,
+}
+
+/// `GET /export?format=csv|geojson|jsonl&from=&to=&species=`
+///
+/// The field list and schema version are sent as `X-Export-Fields` and
+/// `X-Export-Schema-Version`.
+pub async fn export(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, ApiError> {
+    let format: Format = params
+        .format
+        .as_deref()
+        .unwrap_or("csv")
+        .parse()
+        .map_err(ApiError::bad_request)?;
+    let filter = CatchFilter {
+        from: params
+            .from
+            .as_deref()
+            .map(|v| catches::parse_bound(v, false))
+            .transpose()?,
+        to: params
+            .to
+            .as_deref()
+            .map(|v| catches::parse_bound(v, true))
+            .transpose()?,
+        species: params.species,
+    };
+    let body = render(format, &load(&state.db, filter).await?);
+
+    let headers = [
+        (CONTENT_TYPE, format.content_type().to_string()),
+        (
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"catches.{}\"", format),
+        ),
+        (SCHEMA_VERSION_HEADER, SCHEMA_VERSION.to_string()),
+        (FIELDS_HEADER, FIELDS.join(",")),
+    ];
+    Ok((headers, body).into_response())
+}
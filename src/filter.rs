@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::config::{FilterConfig, MeasurementConfig};
+use crate::detector::DetectionResult;
+use crate::measure;
+
+/// Changes a client asks for on its own connection; missing fields stay as
+/// they were.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct FilterUpdate {
+    pub min_confidence: Option<f32>,
+    pub class_min_confidence: Option<HashMap<String, f32>>,
+    pub top_k: Option<usize>,
+    pub allow: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+    pub nms_iou_threshold: Option<f32>,
+    pub class_agnostic_nms: Option<bool>,
+}
+
+impl FilterUpdate {
+    /// `current` with this update applied, or what's wrong with the result.
+    pub fn apply(self, current: &FilterConfig) -> Result<FilterConfig, String> {
+        let mut config = current.clone();
+        if let Some(min_confidence) = self.min_confidence {
+            config.min_confidence = min_confidence;
+        }
+        if let Some(class_min_confidence) = self.class_min_confidence {
+            config.class_min_confidence = class_min_confidence;
+        }
+        if let Some(top_k) = self.top_k {
+            config.top_k = top_k;
+        }
+        if let Some(allow) = self.allow {
+            config.allow = allow;
+        }
+        if let Some(deny) = self.deny {
+            config.deny = deny;
+        }
+        if let Some(nms_iou_threshold) = self.nms_iou_threshold {
+            config.nms_iou_threshold = nms_iou_threshold;
+        }
+        if let Some(class_agnostic_nms) = self.class_agnostic_nms {
+            config.class_agnostic_nms = class_agnostic_nms;
+        }
+        let problems = config.problems();
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+fn listed(classes: &[String], class: &str) -> bool {
+    classes
+        .iter()
+        .any(|listed| listed.eq_ignore_ascii_case(class))
+}
+
+fn min_confidence(config: &FilterConfig, class: &str) -> f32 {
+    config
+        .class_min_confidence
+        .iter()
+        .find(|(listed, _)| listed.eq_ignore_ascii_case(class))
+        .map_or(config.min_confidence, |(_, min)| *min)
+}
+
+/// Drops detections that are unwanted, too unsure or overlapped by a more
+/// confident box, and returns the rest highest confidence first.
+///
+/// The reference object is kept through the allow list and `top_k` so that
+/// narrowing the classes shown doesn't also turn off length estimates.
+pub fn apply(
+    mut detections: Vec<DetectionResult>,
+    config: &FilterConfig,
+    measurement: &MeasurementConfig,
+) -> Vec<DetectionResult> {
+    let is_reference = |detection: &DetectionResult| measure::is_reference(detection, measurement);
+
+    detections.retain(|detection| {
+        let allowed = config.allow.is_empty()
+            || listed(&config.allow, &detection.class)
+            || is_reference(detection);
+        allowed
+            && !listed(&config.deny, &detection.class)
+            && detection.confidence >= min_confidence(config, &detection.class)
+    });
+    detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let mut kept: Vec<DetectionResult> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        let suppressed = detection.bounding_box.is_some_and(|candidate| {
+            kept.iter().any(|other| {
+                let comparable = if config.class_agnostic_nms {
+                    is_reference(other) == is_reference(&detection)
+                } else {
+                    other.class.eq_ignore_ascii_case(&detection.class)
+                };
+                comparable
+                    && other.bounding_box.is_some_and(|kept_box| {
+                        kept_box.pixel.iou(&candidate.pixel) > config.nms_iou_threshold
+                    })
+            })
+        });
+        if !suppressed {
+            kept.push(detection);
+        }
+    }
+
+    if config.top_k > 0 {
+        let mut fish = 0;
+        kept.retain(|detection| {
+            if is_reference(detection) {
+                return true;
+            }
+            fish += 1;
+            fish <= config.top_k
+        });
+    }
+
+    for (index, detection) in kept.iter_mut().enumerate() {
+        detection.index = index;
+    }
+    kept
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::warn;
+
+use crate::location::Location;
+
+/// How long to wait before reopening the device after it fails or disappears.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+/// How often to look for new lines once the end of a plain file is reached.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Verifies the `*hh` checksum when there is one and returns the fields between
+/// `$` and `*`.
+fn sentence_fields(line: &str) -> Option<Vec<&str>> {
+    let body = line.trim().strip_prefix('$')?;
+    let body = match body.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+            let actual = body.bytes().fold(0, |sum, byte| sum ^ byte);
+            if actual != expected {
+                return None;
+            }
+            body
+        }
+        None => body,
+    };
+    Some(body.split(',').collect())
+}
+
+/// Converts NMEA `ddmm.mmmm`/`dddmm.mmmm` plus a hemisphere letter to degrees.
+fn coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let raw: f64 = value.parse().ok()?;
+    let degrees = (raw / 100.0).trunc();
+    let decimal = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// Reads a position out of a GGA or RMC sentence from any talker (`$GP`, `$GN`,
+/// ...). Sentences without a valid fix give `None`.
+pub fn parse_nmea(line: &str) -> Option<Location> {
+    let fields = sentence_fields(line)?;
+    let kind = fields.first()?.get(2..)?;
+    let (lat, lon) = match kind {
+        // $xxGGA,time,lat,N,lon,E,quality,...
+        "GGA" if fields.get(6).is_some_and(|quality| *quality != "0") => (2, 4),
+        // $xxRMC,time,status,lat,N,lon,E,...
+        "RMC" if fields.get(2) == Some(&"A") => (3, 5),
+        _ => return None,
+    };
+    let location = Location {
+        latitude: coordinate(fields.get(lat)?, fields.get(lat + 1)?)?,
+        longitude: coordinate(fields.get(lon)?, fields.get(lon + 1)?)?,
+        accuracy_m: None,
+    };
+    location.check().ok().map(|_| location)
+}
+
+/// Latest fix from an NMEA receiver, kept up to date by a background task.
+#[derive(Clone)]
+pub struct Gps {
+    latest: Arc<RwLock<Option<(Location, Instant)>>>,
+    max_age: Duration,
+}
+
+impl Gps {
+    /// Starts reading NMEA sentences from `device`, which may be a serial port
+    /// (configure its baud rate beforehand, e.g. with `stty`) or a plain file
+    /// being appended to. Fixes older than `max_age` are ignored.
+    pub fn spawn(device: PathBuf, max_age: Duration) -> Self {
+        let gps = Gps {
+            latest: Arc::new(RwLock::new(None)),
+            max_age,
+        };
+        let reader = gps.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = reader.read(&device).await {
+                    warn!(device = %device.display(), error = %err, "GPS device failed");
+                }
+                tokio::time::sleep(REOPEN_DELAY).await;
+            }
+        });
+        gps
+    }
+
+    async fn read(&self, device: &std::path::Path) -> std::io::Result<()> {
+        let file = tokio::fs::File::open(device).await?;
+        let mut lines = BufReader::new(file).lines();
+        loop {
+            match lines.next_line().await? {
+                Some(line) => {
+                    if let Some(location) = parse_nmea(&line) {
+                        if let Ok(mut latest) = self.latest.write() {
+                            *latest = Some((location, Instant::now()));
+                        }
+                    }
+                }
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// The current position, if the receiver has reported one recently.
+    pub fn current(&self) -> Option<Location> {
+        let latest = self.latest.read().ok()?;
+        let (location, at) = (*latest)?;
+        (at.elapsed() <= self.max_age).then_some(location)
+    }
+}
@@ -0,0 +1,242 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::breaker::BreakerStatus;
+use crate::db::DbError;
+use crate::quota::QuotaUsage;
+use crate::AppState;
+
+/// A database that doesn't answer `SELECT 1` within this long counts as down.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Process-wide facts the health endpoints report that nothing else tracks.
+pub struct Health {
+    started: Instant,
+    started_at: DateTime<Utc>,
+    last_inference_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            last_inference_at: Mutex::new(None),
+        }
+    }
+}
+
+impl Health {
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    /// Called whenever the detector answers a frame.
+    pub fn record_inference(&self) {
+        *self.last_inference_at.lock().unwrap() = Some(Utc::now());
+    }
+
+    fn uptime_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+/// Overall verdict: `unavailable` when a frame couldn't be analysed or a catch
+/// couldn't be stored, `degraded` when it could but something is off.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Verdict {
+    Ok,
+    Degraded,
+    Unavailable,
+}
+
+#[derive(Serialize)]
+struct ConfigCheck {
+    ok: bool,
+    /// `None` when running on defaults and the environment alone.
+    source: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct DetectorCheck {
+    ok: bool,
+    backend: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    last_inference_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct DatabaseCheck {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DiskCheck {
+    ok: bool,
+    path: PathBuf,
+    free_bytes: Option<u64>,
+    min_free_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct StatusReport {
+    status: Verdict,
+    version: &'static str,
+    started_at: DateTime<Utc>,
+    uptime_secs: u64,
+    config: ConfigCheck,
+    detector: DetectorCheck,
+    roboflow_circuit: BreakerStatus,
+    roboflow_quota: QuotaUsage,
+    database: DatabaseCheck,
+    disk: DiskCheck,
+}
+
+/// Bytes available to unprivileged writers on the filesystem holding `path`.
+/// The image directory is only created with the first catch, so until then the
+/// nearest existing parent is measured instead.
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` is a plain C
+    // struct that statvfs fills in.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+async fn check_database(state: &AppState) -> DatabaseCheck {
+    let query = state.db.call(|conn| {
+        conn.query_row("SELECT 1", [], |_| Ok(()))
+            .map_err(DbError::from)
+    });
+    let error = match tokio::time::timeout(DATABASE_TIMEOUT, query).await {
+        Ok(Ok(())) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some(format!("no answer within {:?}", DATABASE_TIMEOUT)),
+    };
+    DatabaseCheck {
+        ok: error.is_none(),
+        error,
+    }
+}
+
+fn check_disk(state: &AppState) -> DiskCheck {
+    let path = state.config.storage.image_dir.clone();
+    let min_free_bytes = state.config.storage.min_free_disk_mb * 1024 * 1024;
+    match free_space(&path) {
+        Ok(free_bytes) => DiskCheck {
+            ok: free_bytes >= min_free_bytes,
+            path,
+            free_bytes: Some(free_bytes),
+            min_free_bytes,
+            error: None,
+        },
+        Err(err) => DiskCheck {
+            ok: false,
+            path,
+            free_bytes: None,
+            min_free_bytes,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+async fn report(state: &AppState) -> StatusReport {
+    let detector_error = state.detector.ready().err();
+    let detector = DetectorCheck {
+        ok: detector_error.is_none(),
+        backend: state.detector.name(),
+        error: detector_error,
+        last_inference_at: *state.health.last_inference_at.lock().unwrap(),
+    };
+    let database = check_database(state).await;
+    let disk = check_disk(state);
+    let roboflow_circuit = state.roboflow_breaker.status();
+    let roboflow_quota = state.quota.usage();
+
+    // The config is validated before the server starts, so it can only be ok
+    // here; it's reported so the file actually in use is visible.
+    let config = ConfigCheck {
+        ok: true,
+        source: state.config.source.clone(),
+    };
+
+    let status = if !detector.ok || !database.ok || !disk.ok {
+        Verdict::Unavailable
+    } else if state.roboflow_breaker.is_open() || state.quota.exhausted().is_some() {
+        // Only reachable with a fallback model answering in Roboflow's place.
+        Verdict::Degraded
+    } else {
+        Verdict::Ok
+    };
+
+    StatusReport {
+        status,
+        version: env!("CARGO_PKG_VERSION"),
+        started_at: state.health.started_at,
+        uptime_secs: state.health.uptime_secs(),
+        config,
+        detector,
+        roboflow_circuit,
+        roboflow_quota,
+        database,
+        disk,
+    }
+}
+
+/// Everything the server knows about its own health. Always answers 200 so
+/// the body can be read even when something is down.
+pub async fn status(State(state): State<AppState>) -> Json<StatusReport> {
+    Json(report(&state).await)
+}
+
+#[derive(Serialize)]
+pub struct Liveness {
+    status: Verdict,
+    uptime_secs: u64,
+}
+
+/// Liveness: answers as long as the process is serving requests at all.
+pub async fn healthz(State(state): State<AppState>) -> Json<Liveness> {
+    Json(Liveness {
+        status: Verdict::Ok,
+        uptime_secs: state.health.uptime_secs(),
+    })
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    status: Verdict,
+}
+
+/// Readiness: 503 while frames can't be analysed or catches can't be stored.
+/// Only the verdict is given, since this route needs no key; the details are on
+/// `/status`.
+pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let status = report(&state).await.status;
+    let code = if status == Verdict::Unavailable {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(Readiness { status }))
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::Extension;
+
+use crate::auth::Client;
+use crate::config::LimitsConfig;
+use crate::detector::DetectorError;
+
+/// Beyond this many clients, ones whose buckets have refilled are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(burst: u32) -> Self {
+        Bucket {
+            tokens: burst.into(),
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, rate: f64, burst: u32) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst.into());
+        self.updated = now;
+    }
+
+    /// Takes a token, or says how long until one is available.
+    fn take(&mut self, rate: f64, burst: u32) -> Result<(), Duration> {
+        self.refill(rate, burst);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+/// Token buckets for frames: one per client and one shared by everyone. A
+/// rate of zero turns that bucket off.
+pub struct RateLimiter {
+    config: LimitsConfig,
+    global: Mutex<Bucket>,
+    clients: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &LimitsConfig) -> Self {
+        RateLimiter {
+            config: config.clone(),
+            global: Mutex::new(Bucket::full(config.global_burst)),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Lets one frame from `client` through, or refuses it with how long to
+    /// wait.
+    pub fn check(&self, client: &str) -> Result<(), DetectorError> {
+        let LimitsConfig {
+            client_frames_per_sec: client_rate,
+            client_burst,
+            global_frames_per_sec: global_rate,
+            global_burst,
+        } = self.config;
+
+        let mut clients = self.clients.lock().unwrap();
+        if client_rate > 0.0 {
+            if !clients.contains_key(client) && clients.len() >= MAX_TRACKED_CLIENTS {
+                clients.retain(|_, bucket| {
+                    bucket.refill(client_rate, client_burst);
+                    bucket.tokens < client_burst.into()
+                });
+            }
+            clients
+                .entry(client.to_string())
+                .or_insert_with(|| Bucket::full(client_burst))
+                .take(client_rate, client_burst)
+                .map_err(DetectorError::RateLimited)?;
+        }
+        if global_rate > 0.0 {
+            if let Err(wait) = self.global.lock().unwrap().take(global_rate, global_burst) {
+                // The client's own budget shouldn't pay for a frame that was
+                // refused anyway.
+                if let Some(bucket) = clients.get_mut(client) {
+                    bucket.tokens += 1.0;
+                }
+                return Err(DetectorError::RateLimited(wait));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Who a frame counts against: the API key's client, or the peer's address
+/// when keys aren't in use.
+pub fn client_id(client: Option<&Extension<Client>>, peer: SocketAddr) -> String {
+    match client {
+        Some(Extension(client)) => client.name.clone(),
+        None => peer.ip().to_string(),
+    }
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// A WGS 84 position, as sent by clients or read from the GPS.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    #[serde(alias = "lat")]
+    pub latitude: f64,
+    #[serde(alias = "lon")]
+    pub longitude: f64,
+    /// Horizontal accuracy in metres, when the source reports one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy_m: Option<f32>,
+}
+
+impl Location {
+    /// Rejects positions that can't be on Earth, which usually means the client
+    /// swapped latitude and longitude or sent a placeholder.
+    pub fn check(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(format!("latitude {} is out of range", self.latitude));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(format!("longitude {} is out of range", self.longitude));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Write};
+
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Query parameters whose values never reach the logs.
+const SECRET_PARAMS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "key",
+    "token",
+    "access_token",
+    "secret",
+    "password",
+];
+
+const REDACTED: &str = "REDACTED";
+
+/// `text` with the value of every secret query parameter replaced, so a URL
+/// like Roboflow's, which carries the API key, can be logged as it is.
+pub fn redact(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(['?', '&']) {
+        out.push_str(&rest[..=at]);
+        rest = &rest[at + 1..];
+        let name_len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if !rest[name_len..].starts_with('=')
+            || !SECRET_PARAMS
+                .iter()
+                .any(|secret| name.eq_ignore_ascii_case(secret))
+        {
+            continue;
+        }
+        let value_start = name_len + 1;
+        let value_len = rest[value_start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '&' | '#' | '"' | '\'' | ')' | '>'))
+            .unwrap_or(rest.len() - value_start);
+        out.push_str(&rest[..value_start]);
+        out.push_str(REDACTED);
+        rest = &rest[value_start + value_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Stderr with secrets taken out. The formatter hands over one whole event per
+/// write, so a secret is never split across calls.
+struct RedactingStderr;
+
+impl Write for RedactingStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        io::stderr().write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG`, when set, takes precedence over
+/// `logging.level`. Logs go to stderr so `export` can write to stdout.
+pub fn init(config: &LoggingConfig) -> Result<(), String> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) => {
+            EnvFilter::try_new(directives).map_err(|err| format!("Invalid RUST_LOG: {}", err))?
+        }
+        Err(_) => EnvFilter::try_new(&config.level)
+            .map_err(|err| format!("Invalid logging.level: {}", err))?,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| RedactingStderr);
+    let installed = match config.format {
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    installed.map_err(|err| format!("Failed to set up logging: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+
+    #[test]
+    fn redacts_the_roboflow_url() {
+        assert_eq!(
+            redact("https://detect.roboflow.com/fish/3?api_key=abc123&confidence=40"),
+            "https://detect.roboflow.com/fish/3?api_key=REDACTED&confidence=40"
+        );
+    }
+
+    #[test]
+    fn redacts_a_secret_after_other_parameters() {
+        assert_eq!(
+            redact("/ws?angler_id=1&api_key=efk_1_abc&trip_id=2"),
+            "/ws?angler_id=1&api_key=REDACTED&trip_id=2"
+        );
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_value() {
+        assert_eq!(
+            redact(r#"uri="/ws?token=abc" status=101"#),
+            r#"uri="/ws?token=REDACTED" status=101"#
+        );
+        assert_eq!(
+            redact("Failed to reach Roboflow (url: /x?key=abc) after 3 attempts"),
+            "Failed to reach Roboflow (url: /x?key=REDACTED) after 3 attempts"
+        );
+        assert_eq!(redact("/x?password=hunter2"), "/x?password=REDACTED");
+    }
+
+    #[test]
+    fn leaves_other_parameters_alone() {
+        let text = "/catches?from=2024-06-01&species=trout&monkey=1&keys=2 and a & b";
+        assert_eq!(redact(text), text);
+    }
+
+    #[test]
+    fn redacts_json_events() {
+        let line = concat!(
+            r#"{"timestamp":"2024-06-01T12:00:00Z","level":"WARN","fields":"#,
+            r#"{"message":"Roboflow request failed, retrying","#,
+            r#""error":"error sending request for url (https://detect.roboflow.com/fish/3?api_key=abc123)"},"#,
+            r#""span":{"uri":"/ws?API_KEY=efk_1_abc"}}"#,
+            "\n"
+        );
+        let expected = concat!(
+            r#"{"timestamp":"2024-06-01T12:00:00Z","level":"WARN","fields":"#,
+            r#"{"message":"Roboflow request failed, retrying","#,
+            r#""error":"error sending request for url (https://detect.roboflow.com/fish/3?api_key=REDACTED)"},"#,
+            r#""span":{"uri":"/ws?API_KEY=REDACTED"}}"#,
+            "\n"
+        );
+        assert_eq!(redact(line), expected);
+    }
+}
@@ -1,149 +1,264 @@
+mod api;
+mod auth;
+mod bag;
+mod breaker;
+mod catches;
+mod cli;
+mod config;
+mod db;
+mod detector;
+mod export;
+mod filter;
+mod gps;
+mod health;
+mod limits;
+mod location;
+mod logging;
+mod measure;
+mod metrics;
+mod pipeline;
+mod preprocess;
+mod protocol;
+mod quota;
+mod regulations;
+mod tracker;
+mod trips;
+mod upload;
+mod ws;
+mod zones;
+
+use auth::KeyStore;
 use axum::{
-    extract::{
-        ws::{WebSocket, WebSocketUpgrade},
-        Multipart,
-    },
-    response::IntoResponse,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{get, post},
-    Json, Router,
+    Router,
 };
-use futures::{sink::SinkExt, stream::StreamExt};
-use image::ImageFormat;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::io::Cursor;
+use breaker::CircuitBreaker;
+use catches::Catch;
+use cli::Command;
+use config::Config;
+use db::Database;
+use detector::Detector;
+use gps::Gps;
+use health::Health;
+use limits::RateLimiter;
+use metrics::Metrics;
+use quota::Quota;
+use regulations::Regulations;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tch::{
-    nn,
-    vision::{imagenet, resnet},
-    Device,
-};
-
-#[derive(Serialize)]
-struct StatusResponse {
-    status: String,
-    version: String,
-}
-
-#[derive(Deserialize, Debug)]
-struct RoboflowPrediction {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    confidence: f32,
-    class: String,
-    class_id: u32,
-}
-
-#[derive(Deserialize, Debug)]
-struct RoboflowResponse {
-    predictions: Vec<RoboflowPrediction>,
-}
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+use zones::Zones;
 
-#[derive(Serialize, Deserialize, Debug)]
-struct DetectionResult {
-    class: String,
-    confidence: f32,
+/// Shared with every handler through axum's `State`.
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    detector: Arc<dyn Detector>,
+    roboflow_breaker: Arc<CircuitBreaker>,
+    regulations: Option<Arc<Regulations>>,
+    zones: Option<Arc<Zones>>,
+    gps: Option<Gps>,
+    db: Database,
+    /// Every catch logged, for WebSocket clients subscribed to `catches`.
+    catch_events: broadcast::Sender<Catch>,
+    health: Arc<Health>,
+    metrics: Arc<Metrics>,
+    /// `None` when `auth.keys_path` isn't set and every client is let in.
+    keys: Option<Arc<KeyStore>>,
+    quota: Arc<Quota>,
+    rate_limiter: Arc<RateLimiter>,
 }
 
-async fn status() -> Json<StatusResponse> {
-    Json(StatusResponse {
-        status: "ok".to_string(),
-        version: env!("CARGO_PKG_VERSION").to_string(),
-    })
-}
-
-async fn process_image(base64_image: &str) -> Vec<DetectionResult> {
-    let api_key = std::env::var("ROBOFLOW_API_KEY").unwrap();
-    let model_id = std::env::var("ROBOFLOW_MODEL_ID").unwrap();
-    let model_version = std::env::var("ROBOFLOW_MODEL_VERSION").unwrap();
-    let url = format!(
-        "https://detect.roboflow.com/{}/{}?api_key={}",
-        model_id, model_version, api_key
-    );
-
-    let client = Client::new();
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(format!("image={}", base64_image)) // Correctly formatting the body
-        .send()
-        .await;
+#[tokio::main]
+async fn main() {
+    let command = cli::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, cli::USAGE);
+        std::process::exit(2);
+    });
+    if let Command::Help = command {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-    match response {
-        Ok(resp) if resp.status().is_success() => match resp.json::<RoboflowResponse>().await {
-            Ok(json_response) => json_response
-                .predictions
-                .into_iter()
-                .map(|p| DetectionResult {
-                    class: p.class,
-                    confidence: p.confidence,
-                })
-                .collect(),
-            Err(_) => {
-                eprintln!("Failed to parse Roboflow response.");
-                vec![]
+    let config = match command {
+        Command::Serve => Config::load(),
+        _ => Config::load_for_cli(),
+    };
+    let config = config.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    if let Err(err) = logging::init(&config.logging) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    match command {
+        Command::Export(args) => {
+            if let Err(err) = cli::export(&config, args).await {
+                eprintln!("{}", err);
+                std::process::exit(1);
             }
-        },
-        Ok(resp) => {
-            let error_text = resp
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            eprintln!("Roboflow API error: {}", error_text);
-            vec![]
+            return;
         }
-        Err(err) => {
-            eprintln!("Failed to send request: {:?}", err);
-            vec![]
+        Command::Keys(args) => {
+            if let Err(err) = cli::keys(&config, args) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            return;
         }
+        Command::Serve | Command::Help => {}
     }
-}
+    for warning in config.warnings() {
+        warn!("{}", warning);
+    }
+    // One client for the whole process so TLS sessions and connections are reused.
+    let http = reqwest::Client::builder()
+        .connect_timeout(Duration::from_millis(config.roboflow.connect_timeout_ms))
+        .timeout(Duration::from_millis(config.roboflow.request_timeout_ms))
+        .build()
+        .unwrap_or_else(|err| {
+            error!("Failed to build HTTP client: {}", err);
+            std::process::exit(1);
+        });
+    let roboflow_breaker = Arc::new(CircuitBreaker::new(
+        config.roboflow.breaker_failure_threshold,
+        Duration::from_millis(config.roboflow.breaker_reset_ms),
+    ));
+    let db = Database::open(&config.storage.database_path).unwrap_or_else(|err| {
+        error!("{}", err);
+        std::process::exit(1);
+    });
+    let quota = Quota::load(db.clone(), &config.roboflow)
+        .await
+        .unwrap_or_else(|err| {
+            error!("{}", err);
+            std::process::exit(1);
+        });
+    let quota = Arc::new(quota);
 
-async fn handle_socket(mut socket: WebSocket) {
-    while let Some(msg) = socket.recv().await {
-        let msg = if let Ok(msg) = msg {
-            msg
-        } else {
-            return;
-        };
+    let metrics = Arc::new(Metrics::default());
+    let detector = detector::from_config(&config, &http, &roboflow_breaker, &metrics, &quota)
+        .unwrap_or_else(|err| {
+            error!("{}", err);
+            std::process::exit(1);
+        });
+    info!("Using {} detector", detector.name());
 
-        if let Ok(text) = msg.to_text() {
-            if text == "ping" {
-                if socket.send("pong".into()).await.is_err() {
-                    return;
+    let regulations = match &config.regulations.path {
+        Some(path) => {
+            let regulations = Regulations::load(path).unwrap_or_else(|err| {
+                error!("{}", err);
+                std::process::exit(1);
+            });
+            if let Some(jurisdiction) = &config.regulations.jurisdiction {
+                if regulations.jurisdiction(jurisdiction).is_none() {
+                    error!(
+                        "Jurisdiction '{}' is not in {}",
+                        jurisdiction,
+                        path.display()
+                    );
+                    std::process::exit(1);
                 }
-                continue;
             }
+            Some(Arc::new(regulations))
+        }
+        None => None,
+    };
 
-            // Process image if it's base64 encoded
-            if text.starts_with("data:image") {
-                let base64_image = text.split(",").nth(1).unwrap_or("");
-                let results = process_image(base64_image).await;
-
-                if let Ok(json) = serde_json::to_string(&results) {
-                    if socket.send(json.into()).await.is_err() {
-                        return;
-                    }
-                }
+    let zones = config.regulations.zones_path.as_ref().map(|path| {
+        let zones = Zones::load(path).unwrap_or_else(|err| {
+            error!("{}", err);
+            std::process::exit(1);
+        });
+        let regulations = regulations.as_deref();
+        for jurisdiction in zones.jurisdictions() {
+            if regulations.is_some_and(|r| r.jurisdiction(jurisdiction).is_none()) {
+                error!(
+                    "Zone jurisdiction '{}' in {} is not in the regulations",
+                    jurisdiction,
+                    path.display()
+                );
+                std::process::exit(1);
             }
         }
-    }
-}
+        Arc::new(zones)
+    });
 
-async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(handle_socket)
-}
+    let gps = config.gps.device.clone().map(|device| {
+        info!("Reading GPS from {}", device.display());
+        Gps::spawn(device, Duration::from_millis(config.gps.max_fix_age_ms))
+    });
 
-#[tokio::main]
-async fn main() {
+    let keys = match &config.auth.keys_path {
+        Some(path) => {
+            let keys = KeyStore::open(path.clone()).unwrap_or_else(|err| {
+                error!("{}", err);
+                std::process::exit(1);
+            });
+            info!("Requiring API keys from {}", path.display());
+            Some(Arc::new(keys))
+        }
+        None => {
+            warn!("auth.keys_path is not set; anyone who can reach the server can use it");
+            None
+        }
+    };
+
+    let rate_limiter = Arc::new(RateLimiter::new(&config.limits));
+    let bind = config.server.bind;
+    let state = AppState {
+        config: Arc::new(config),
+        detector,
+        roboflow_breaker,
+        regulations,
+        zones,
+        gps,
+        db,
+        catch_events: broadcast::channel(16).0,
+        health: Arc::new(Health::default()),
+        metrics,
+        keys,
+        quota,
+        rate_limiter,
+    };
     let app = Router::new()
-        .route("/status", get(status))
-        .route("/ws", get(ws_handler));
+        .route("/status", get(health::status))
+        .route("/metrics", get(metrics::metrics))
+        .route("/ws", get(ws::handler))
+        .route("/detect", post(upload::detect))
+        .route("/catches", get(catches::list).post(catches::create))
+        .route("/catches/:id", get(catches::get))
+        .route("/export", get(export::export))
+        .route(
+            "/anglers",
+            get(trips::list_anglers).post(trips::create_angler),
+        )
+        .route("/trips", post(trips::create_trip))
+        .route("/trips/:id", get(trips::get_trip))
+        .route("/trips/:id/end", post(trips::end_trip))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_key,
+        ))
+        // Left open so supervisors and load balancers can probe without a key.
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .layer(DefaultBodyLimit::max(state.config.server.max_upload_bytes))
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Server running on http://0.0.0.0:3000");
+    let listener = tokio::net::TcpListener::bind(bind).await.unwrap();
+    info!("Server running on http://{}", bind);
 
-    axum::serve(listener, app).await.unwrap();
+    // Peer addresses tell clients apart for rate limiting when keys are off.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
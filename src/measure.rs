@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::MeasurementConfig;
+use crate::detector::{DetectionResult, Rect};
+
+const CM_PER_INCH: f32 = 2.54;
+
+/// Estimated total length of a fish.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub cm: f32,
+    pub inches: f32,
+    /// One standard deviation, in cm.
+    pub uncertainty_cm: f32,
+}
+
+/// Fish and boards are photographed lying flat, so the longer side of the box
+/// is the one along the body.
+fn long_side(rect: &Rect) -> f32 {
+    rect.width.max(rect.height)
+}
+
+pub fn is_reference(detection: &DetectionResult, config: &MeasurementConfig) -> bool {
+    config
+        .reference_class
+        .as_deref()
+        .is_some_and(|class| class.eq_ignore_ascii_case(&detection.class))
+}
+
+/// Fills in `length` for every boxed detection in a frame that also contains
+/// the configured reference object (a measuring board or printed marker the
+/// detector is trained to find). Frames without a reference are left alone.
+pub fn estimate_lengths(detections: &mut [DetectionResult], config: &MeasurementConfig) {
+    let reference_px = detections
+        .iter()
+        .filter(|d| is_reference(d, config))
+        .filter_map(|d| d.bounding_box.map(|b| (d.confidence, long_side(&b.pixel))))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, px)| px);
+    let Some(reference_px) = reference_px.filter(|px| *px > 0.0) else {
+        return;
+    };
+    let px_per_cm = reference_px / config.reference_length_cm;
+    let reference_error = config.box_error_px / reference_px;
+
+    for detection in detections.iter_mut() {
+        if is_reference(detection, config) {
+            continue;
+        }
+        let Some(bounding_box) = detection.bounding_box else {
+            continue;
+        };
+        let fish_px = long_side(&bounding_box.pixel);
+        if fish_px <= 0.0 {
+            continue;
+        }
+
+        let cm = fish_px / px_per_cm;
+        // Independent relative errors from both boxes and from how tightly a box
+        // fits a fish's outline, added in quadrature.
+        let fish_error = config.box_error_px / fish_px;
+        let relative =
+            (reference_error.powi(2) + fish_error.powi(2) + config.fit_error.powi(2)).sqrt();
+        detection.length = Some(Length {
+            cm,
+            inches: cm / CM_PER_INCH,
+            uncertainty_cm: cm * relative,
+        });
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse};
+
+use crate::AppState;
+
+/// Upper bounds of the inference latency buckets, in seconds. Local models on
+/// a Pi land in the middle; Roboflow round trips toward the top.
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, not yet cumulative; the last counts `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Counters for `GET /metrics`, shared by everything that has something to count.
+#[derive(Default)]
+pub struct Metrics {
+    frames_received: AtomicU64,
+    frames_processed: AtomicU64,
+    frames_dropped: AtomicU64,
+    websocket_connections: AtomicI64,
+    /// Keyed by backend name.
+    inference_seconds: Mutex<BTreeMap<&'static str, Histogram>>,
+    /// Keyed by HTTP status, or `timeout`, `network` or `parse` when there was
+    /// no usable answer.
+    roboflow_errors: Mutex<BTreeMap<String, u64>>,
+    /// Keyed by species, once per object rather than per frame it's seen in.
+    detections: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Metrics {
+    pub fn frame_received(&self) {
+        self.frames_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame_processed(&self) {
+        self.frames_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame_dropped(&self) {
+        self.frames_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn websocket_opened(&self) {
+        self.websocket_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn websocket_closed(&self) {
+        self.websocket_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn inference(&self, backend: &'static str, elapsed: Duration) {
+        self.inference_seconds
+            .lock()
+            .unwrap()
+            .entry(backend)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn roboflow_error(&self, status: impl Into<String>) {
+        *self
+            .roboflow_errors
+            .lock()
+            .unwrap()
+            .entry(status.into())
+            .or_default() += 1;
+    }
+
+    pub fn detection(&self, species: &str) {
+        let mut detections = self.detections.lock().unwrap();
+        match detections.get_mut(species) {
+            Some(count) => *count += 1,
+            None => {
+                detections.insert(species.to_string(), 1);
+            }
+        }
+    }
+}
+
+/// Escapes a label value for the text exposition format.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// CPU time, resident and virtual memory of this process, from `/proc`.
+/// `None` off Linux or if the file can't be read.
+fn process_stats() -> Option<(f64, u64, u64)> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so count fields from after it.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+    let (utime, stime, vsize, rss) = (field(14)?, field(15)?, field(23)?, field(24)?);
+
+    // SAFETY: sysconf only reads a system constant.
+    let (ticks, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    if ticks <= 0 || page_size <= 0 {
+        return None;
+    }
+    let cpu_seconds = (utime + stime) as f64 / ticks as f64;
+    Some((cpu_seconds, rss * page_size as u64, vsize))
+}
+
+fn render(state: &AppState) -> String {
+    let metrics = &state.metrics;
+    let mut out = String::new();
+
+    for (name, help, counter) in [
+        (
+            "ethicalfish_frames_received_total",
+            "Frames received over the WebSocket and /detect.",
+            &metrics.frames_received,
+        ),
+        (
+            "ethicalfish_frames_processed_total",
+            "Frames analysed successfully.",
+            &metrics.frames_processed,
+        ),
+        (
+            "ethicalfish_frames_dropped_total",
+            "WebSocket frames skipped because a newer frame replaced or overtook them.",
+            &metrics.frames_dropped,
+        ),
+    ] {
+        header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+    }
+
+    header(
+        &mut out,
+        "ethicalfish_websocket_connections",
+        "gauge",
+        "Open WebSocket connections.",
+    );
+    let _ = writeln!(
+        out,
+        "ethicalfish_websocket_connections {}",
+        metrics.websocket_connections.load(Ordering::Relaxed)
+    );
+
+    header(
+        &mut out,
+        "ethicalfish_inference_duration_seconds",
+        "histogram",
+        "Time each detector backend took per frame.",
+    );
+    for (backend, histogram) in metrics.inference_seconds.lock().unwrap().iter() {
+        let backend = label(backend);
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "ethicalfish_inference_duration_seconds_bucket{{backend=\"{}\",le=\"{}\"}} {}",
+                backend, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "ethicalfish_inference_duration_seconds_bucket{{backend=\"{}\",le=\"+Inf\"}} {}",
+            backend, histogram.count
+        );
+        let _ = writeln!(
+            out,
+            "ethicalfish_inference_duration_seconds_sum{{backend=\"{}\"}} {}",
+            backend, histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "ethicalfish_inference_duration_seconds_count{{backend=\"{}\"}} {}",
+            backend, histogram.count
+        );
+    }
+
+    header(
+        &mut out,
+        "ethicalfish_roboflow_errors_total",
+        "counter",
+        "Failed Roboflow requests, including retried ones, by HTTP status.",
+    );
+    for (status, count) in metrics.roboflow_errors.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "ethicalfish_roboflow_errors_total{{status=\"{}\"}} {}",
+            label(status),
+            count
+        );
+    }
+
+    let quota = state.quota.usage();
+    for (name, help, daily, monthly) in [
+        (
+            "ethicalfish_roboflow_quota_calls",
+            "Roboflow calls counted against the current day's and month's budget.",
+            quota.daily_calls,
+            quota.monthly_calls,
+        ),
+        (
+            "ethicalfish_roboflow_quota_limit",
+            "Roboflow call budget per day and month; 0 means no limit.",
+            quota.daily_limit,
+            quota.monthly_limit,
+        ),
+    ] {
+        header(&mut out, name, "gauge", help);
+        let _ = writeln!(out, "{}{{period=\"day\"}} {}", name, daily);
+        let _ = writeln!(out, "{}{{period=\"month\"}} {}", name, monthly);
+    }
+
+    header(
+        &mut out,
+        "ethicalfish_detections_total",
+        "counter",
+        "Objects detected, by species; a tracked object counts once.",
+    );
+    for (species, count) in metrics.detections.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "ethicalfish_detections_total{{species=\"{}\"}} {}",
+            label(species),
+            count
+        );
+    }
+
+    if let Some((cpu_seconds, resident_bytes, virtual_bytes)) = process_stats() {
+        header(
+            &mut out,
+            "process_cpu_seconds_total",
+            "counter",
+            "User and system CPU time spent.",
+        );
+        let _ = writeln!(out, "process_cpu_seconds_total {}", cpu_seconds);
+        header(
+            &mut out,
+            "process_resident_memory_bytes",
+            "gauge",
+            "Resident memory size.",
+        );
+        let _ = writeln!(out, "process_resident_memory_bytes {}", resident_bytes);
+        header(
+            &mut out,
+            "process_virtual_memory_bytes",
+            "gauge",
+            "Virtual memory size.",
+        );
+        let _ = writeln!(out, "process_virtual_memory_bytes {}", virtual_bytes);
+    }
+    header(
+        &mut out,
+        "process_start_time_seconds",
+        "gauge",
+        "Start time of the process since the Unix epoch.",
+    );
+    let _ = writeln!(
+        out,
+        "process_start_time_seconds {}",
+        state.health.started_at().timestamp()
+    );
+
+    out
+}
+
+/// `GET /metrics`: everything above in the Prometheus text format.
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    ([(CONTENT_TYPE, CONTENT_TYPE_TEXT)], render(&state))
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::NaiveDate;
+use image::ImageFormat;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::bag;
+use crate::config::FilterConfig;
+use crate::detector::{DetectionResult, DetectorError};
+use crate::filter;
+use crate::location::Location;
+use crate::measure;
+use crate::preprocess::{self, ImageInfo};
+use crate::tracker::Tracker;
+use crate::trips::TripContext;
+use crate::AppState;
+
+/// Formats a frame may arrive in, checked by sniffing the bytes rather than
+/// trusting whatever the client claims.
+const SUPPORTED_FORMATS: [ImageFormat; 3] =
+    [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+fn check_format(image: &[u8]) -> Result<ImageFormat, DetectorError> {
+    match image::guess_format(image) {
+        Ok(format) if SUPPORTED_FORMATS.contains(&format) => Ok(format),
+        Ok(format) => Err(DetectorError::InvalidImage(format!(
+            "unsupported format {:?}, expected JPEG, PNG or WebP",
+            format
+        ))),
+        Err(err) => Err(DetectorError::InvalidImage(err.to_string())),
+    }
+}
+
+/// Decodes either a bare base64 string or a `data:image/...;base64,` URL.
+pub fn decode_base64(data: &str) -> Result<Vec<u8>, DetectorError> {
+    let base64_image = match data.split_once(',') {
+        Some((prefix, rest)) if prefix.starts_with("data:") => rest,
+        _ => data,
+    };
+    STANDARD
+        .decode(base64_image.trim())
+        .map_err(|err| DetectorError::InvalidImage(err.to_string()))
+}
+
+/// Who a frame belongs to and where it was taken, carried from the connection
+/// or request.
+#[derive(Debug, Clone, Default)]
+pub struct FrameContext {
+    pub trip: TripContext,
+    /// Position the client sent; the GPS fix is used when this is absent.
+    pub location: Option<Location>,
+    /// The connection's own filter settings, replacing the configured ones.
+    pub filter: Option<FilterConfig>,
+    /// Who the frame counts against for rate limiting; see `limits::client_id`.
+    pub client: String,
+}
+
+impl FrameContext {
+    /// The client's position, falling back to the local GPS receiver.
+    pub fn location(&self, state: &AppState) -> Option<Location> {
+        self.location
+            .or_else(|| state.gps.as_ref().and_then(|gps| gps.current()))
+    }
+}
+
+/// The zone containing `location`, or the configured jurisdiction outside every
+/// zone and when the position is unknown.
+fn jurisdiction_at<'a>(state: &'a AppState, location: Option<&Location>) -> Option<&'a str> {
+    location
+        .and_then(|location| state.zones.as_ref()?.lookup(location))
+        .or(state.config.regulations.jurisdiction.as_deref())
+}
+
+/// Gives every detection a verdict under the regulations in force at `location`
+/// on `date`, then checks it against the angler's bag limits. Does nothing
+/// without regulations or a jurisdiction.
+pub async fn assess(
+    state: &AppState,
+    detections: &mut [DetectionResult],
+    location: Option<&Location>,
+    trip: TripContext,
+    date: NaiveDate,
+) {
+    let (Some(regulations), Some(jurisdiction)) =
+        (&state.regulations, jurisdiction_at(state, location))
+    else {
+        return;
+    };
+    for detection in detections.iter_mut() {
+        if measure::is_reference(detection, &state.config.measurement) {
+            continue;
+        }
+        detection.assessment =
+            Some(regulations.assess(jurisdiction, detection.species(), detection.length, date));
+    }
+
+    // Bag limits only make the result more cautious, so a storage hiccup
+    // shouldn't cost the angler the whole frame.
+    if let Err(err) =
+        bag::apply_limits(&state.db, regulations, jurisdiction, trip, detections).await
+    {
+        warn!(error = %err, "Failed to check bag limits");
+    }
+}
+
+/// Counts each object once: a track when it first appears, and otherwise every
+/// box but only the top guess of a whole-frame classifier, whose other guesses
+/// are about the same fish.
+fn count_detections(state: &AppState, detections: &[DetectionResult]) {
+    let mut tracks = HashSet::new();
+    let mut counted_frame = false;
+    for detection in detections {
+        if measure::is_reference(detection, &state.config.measurement) {
+            continue;
+        }
+        let new = match (&detection.track, &detection.bounding_box) {
+            (Some(track), _) => track.frames == 1 && tracks.insert(track.id),
+            (None, Some(_)) => true,
+            // Filtering leaves the most confident first.
+            (None, None) => {
+                let first = !counted_frame;
+                counted_frame = true;
+                first
+            }
+        };
+        if new {
+            state.metrics.detection(detection.species());
+        }
+    }
+}
+
+/// What a frame turned into.
+#[derive(Serialize, Debug)]
+pub struct Analysis {
+    pub detections: Vec<DetectionResult>,
+    pub image: ImageInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+/// Runs one frame through preprocessing and detection. Both the WebSocket and the
+/// HTTP upload route go through here so they always behave the same; only the
+/// WebSocket has a `tracker`, since single uploads have no previous frames. It
+/// comes with the frame's sequence number on the connection.
+pub async fn run(
+    state: &AppState,
+    image: Vec<u8>,
+    context: &FrameContext,
+    tracker: Option<(&Mutex<Tracker>, u64)>,
+) -> Result<Analysis, DetectorError> {
+    state.rate_limiter.check(&context.client)?;
+    check_format(&image)?;
+
+    let config = state.config.clone();
+    let processed =
+        tokio::task::spawn_blocking(move || preprocess::preprocess(&image, &config.preprocess))
+            .await
+            .map_err(|err| {
+                DetectorError::InvalidImage(format!("Preprocessing task failed: {}", err))
+            })??;
+
+    let detections = state.detector.detect(&processed.image).await?;
+    state.health.record_inference();
+    let settings = context.filter.as_ref().unwrap_or(&state.config.filter);
+    let mut detections = filter::apply(detections, settings, &state.config.measurement);
+
+    // Measured before rescaling: `box_error_px` is the detector's error, in the
+    // pixels of the frame it was given.
+    measure::estimate_lengths(&mut detections, &state.config.measurement);
+
+    // Map boxes from the resized frame back onto the one the client sent.
+    let scale = processed.info.scale;
+    for detection in &mut detections {
+        if let Some(bounding_box) = &mut detection.bounding_box {
+            bounding_box.pixel = bounding_box.pixel.scaled(1.0 / scale);
+        }
+    }
+    if let Some((tracker, sequence)) = tracker {
+        tracker.lock().unwrap().update(sequence, &mut detections);
+    }
+
+    let location = context.location(state);
+    let today = chrono::Local::now().date_naive();
+    assess(
+        state,
+        &mut detections,
+        location.as_ref(),
+        context.trip,
+        today,
+    )
+    .await;
+
+    debug!(
+        detections = detections.len(),
+        location = location.is_some(),
+        "Frame analysed"
+    );
+    state.metrics.frame_processed();
+    count_detections(state, &detections);
+
+    Ok(Analysis {
+        detections,
+        image: processed.info,
+        location,
+    })
+}
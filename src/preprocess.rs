@@ -0,0 +1,121 @@
+use std::io::Cursor;
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, ColorType, DynamicImage};
+use serde::Serialize;
+
+use crate::config::PreprocessConfig;
+use crate::detector::DetectorError;
+
+/// Size of the frame as sent and as passed to the detector.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct ImageInfo {
+    /// Source dimensions after EXIF orientation has been applied.
+    pub width: u32,
+    pub height: u32,
+    pub processed_width: u32,
+    pub processed_height: u32,
+    /// Processed size divided by source size. Pixel boxes in responses have
+    /// already been divided by this, so they refer to the source image.
+    pub scale: f32,
+}
+
+pub struct Preprocessed {
+    /// JPEG bytes to hand to the detector.
+    pub image: Vec<u8>,
+    pub info: ImageInfo,
+}
+
+fn decode_error(err: impl ToString) -> DetectorError {
+    DetectorError::InvalidImage(err.to_string())
+}
+
+/// EXIF orientation tag, 1 (upright) when absent or unreadable.
+fn exif_orientation(image: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(image))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Decodes, orients and downsizes an image into a JPEG.
+fn shrink_to_jpeg(
+    image: &[u8],
+    max_dimension: u32,
+    quality: u8,
+) -> Result<Preprocessed, DetectorError> {
+    let decoded = image::load_from_memory(image).map_err(decode_error)?;
+    let oriented = apply_orientation(decoded, exif_orientation(image));
+    let (width, height) = (oriented.width(), oriented.height());
+
+    let resized = if width.max(height) > max_dimension {
+        oriented.resize(max_dimension, max_dimension, FilterType::Triangle)
+    } else {
+        oriented
+    };
+    let (resized_width, resized_height) = (resized.width(), resized.height());
+
+    // JPEG has no alpha channel, so flatten to RGB first.
+    let rgb = resized.to_rgb8();
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, quality)
+        .encode(rgb.as_raw(), resized_width, resized_height, ColorType::Rgb8)
+        .map_err(decode_error)?;
+
+    Ok(Preprocessed {
+        image: encoded,
+        info: ImageInfo {
+            width,
+            height,
+            processed_width: resized_width,
+            processed_height: resized_height,
+            scale: resized_width as f32 / width.max(1) as f32,
+        },
+    })
+}
+
+/// Small upright JPEG of `image` for the catch log. Blocking, like `preprocess`.
+pub fn thumbnail(image: &[u8], max_dimension: u32) -> Result<Vec<u8>, DetectorError> {
+    shrink_to_jpeg(image, max_dimension, 80).map(|thumbnail| thumbnail.image)
+}
+
+/// Decodes the frame, turns it upright, shrinks it so neither side exceeds
+/// `max_dimension` and re-encodes it as JPEG. This is CPU bound, so call it from
+/// `spawn_blocking`.
+pub fn preprocess(image: &[u8], config: &PreprocessConfig) -> Result<Preprocessed, DetectorError> {
+    if !config.enabled {
+        let (width, height) = image::io::Reader::new(Cursor::new(image))
+            .with_guessed_format()
+            .map_err(decode_error)?
+            .into_dimensions()
+            .map_err(decode_error)?;
+        return Ok(Preprocessed {
+            image: image.to_vec(),
+            info: ImageInfo {
+                width,
+                height,
+                processed_width: width,
+                processed_height: height,
+                scale: 1.0,
+            },
+        });
+    }
+
+    shrink_to_jpeg(image, config.max_dimension, config.jpeg_quality)
+}
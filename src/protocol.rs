@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::catches::Catch;
+use crate::config::FilterConfig;
+use crate::detector::DetectorError;
+use crate::filter::FilterUpdate;
+use crate::location::Location;
+use crate::pipeline::Analysis;
+
+/// Bumped whenever a message changes shape in a way old clients would trip over.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Oldest version a client can negotiate in `hello`. Every answer is sent in
+/// the current shape, so nothing older can be offered. Version 1 clients never
+/// send `hello`; their bare frames and text `ping` are still accepted.
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// What the server offers, listed in its `hello`.
+pub const CAPABILITIES: &[&str] = &[
+    "binary_frames",
+    "tracking",
+    "filtering",
+    "location",
+    "confirm_catch",
+    "subscribe",
+];
+
+/// Events a client can subscribe to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    /// Every catch logged, from any client.
+    Catches,
+}
+
+/// Every message type a client may send, checked before parsing so unknown ones
+/// get their own error code.
+pub const CLIENT_MESSAGE_TYPES: &[&str] = &[
+    "hello",
+    "frame",
+    "configure",
+    "confirm_catch",
+    "ping",
+    "subscribe",
+];
+
+/// JSON text messages from a WebSocket client, tagged by `type`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ClientMessage {
+    /// Opens the conversation: the protocol versions the client speaks and what
+    /// it can handle.
+    Hello {
+        request_id: Option<String>,
+        #[serde(default)]
+        versions: Vec<u32>,
+        #[serde(default)]
+        capabilities: Vec<String>,
+        client: Option<String>,
+    },
+    /// A base64 image, optionally as a `data:` URL.
+    Frame {
+        request_id: Option<String>,
+        image: String,
+        /// Also becomes the connection's location for later frames.
+        location: Option<Location>,
+    },
+    /// Changes settings for the rest of the connection; absent fields are left
+    /// as they are.
+    Configure {
+        request_id: Option<String>,
+        location: Option<Location>,
+        filter: Option<FilterUpdate>,
+        angler_id: Option<i64>,
+        trip_id: Option<i64>,
+    },
+    /// Logs a detection from the most recent frame as a catch, with that frame
+    /// as its thumbnail. Picks by `track_id` when given, else by `index`.
+    ConfirmCatch {
+        request_id: Option<String>,
+        index: Option<usize>,
+        track_id: Option<u64>,
+        caught_at: Option<DateTime<Utc>>,
+    },
+    Ping {
+        request_id: Option<String>,
+    },
+    Subscribe {
+        request_id: Option<String>,
+        topics: Vec<Topic>,
+    },
+}
+
+/// Everything the server sends back over the WebSocket, and the envelope
+/// `/detect` answers with.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Hello {
+        /// The version both sides will speak.
+        version: u32,
+        request_id: String,
+        server: String,
+        capabilities: &'static [&'static str],
+    },
+    Result {
+        version: u32,
+        request_id: String,
+        #[serde(flatten)]
+        analysis: Analysis,
+    },
+    Error {
+        version: u32,
+        request_id: String,
+        code: &'static str,
+        message: String,
+    },
+    Pong {
+        version: u32,
+        request_id: String,
+    },
+    /// A frame that was skipped because a newer one arrived before it could be
+    /// analysed; it gets no result.
+    Dropped {
+        version: u32,
+        request_id: String,
+        /// Frames dropped so far on this connection.
+        dropped_frames: u64,
+    },
+    /// The connection's settings after a `configure`.
+    Configured {
+        version: u32,
+        request_id: String,
+        angler_id: Option<i64>,
+        trip_id: Option<i64>,
+        location: Option<Location>,
+        filter: FilterConfig,
+    },
+    Subscribed {
+        version: u32,
+        request_id: String,
+        topics: Vec<Topic>,
+    },
+    /// Answer to `confirm_catch`.
+    Catch {
+        version: u32,
+        request_id: String,
+        catch: Catch,
+    },
+    /// A catch logged anywhere, for `catches` subscribers.
+    CatchLogged {
+        version: u32,
+        catch: Catch,
+    },
+}
+
+impl Response {
+    pub fn result(request_id: String, analysis: Analysis) -> Self {
+        Response::Result {
+            version: PROTOCOL_VERSION,
+            request_id,
+            analysis,
+        }
+    }
+
+    pub fn error(request_id: String, err: &DetectorError) -> Self {
+        Response::failure(request_id, err.code(), err.to_string())
+    }
+
+    pub fn failure(request_id: String, code: &'static str, message: String) -> Self {
+        Response::Error {
+            version: PROTOCOL_VERSION,
+            request_id,
+            code,
+            message,
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::Mutex;
+
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::RoboflowConfig;
+use crate::db::{Database, DbError};
+use crate::detector::DetectorError;
+
+/// Roboflow calls made so far this UTC day and month.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct QuotaUsage {
+    pub day: String,
+    pub daily_calls: u64,
+    /// Zero means no daily limit.
+    pub daily_limit: u64,
+    pub month: String,
+    pub monthly_calls: u64,
+    /// Zero means no monthly limit.
+    pub monthly_limit: u64,
+}
+
+impl QuotaUsage {
+    /// Starts the counts afresh once a new day or month has begun.
+    fn roll_over(&mut self) {
+        let (day, month) = periods();
+        if self.day != day {
+            self.day = day;
+            self.daily_calls = 0;
+        }
+        if self.month != month {
+            self.month = month;
+            self.monthly_calls = 0;
+        }
+    }
+
+    fn exhausted(&self) -> Option<String> {
+        if self.daily_limit > 0 && self.daily_calls >= self.daily_limit {
+            return Some(format!(
+                "daily budget of {} Roboflow calls used up",
+                self.daily_limit
+            ));
+        }
+        if self.monthly_limit > 0 && self.monthly_calls >= self.monthly_limit {
+            return Some(format!(
+                "monthly budget of {} Roboflow calls used up",
+                self.monthly_limit
+            ));
+        }
+        None
+    }
+}
+
+fn periods() -> (String, String) {
+    let now = Utc::now();
+    (
+        now.format("%Y-%m-%d").to_string(),
+        now.format("%Y-%m").to_string(),
+    )
+}
+
+async fn calls(db: &Database, period: String) -> Result<u64, DbError> {
+    db.call(move |conn| {
+        let calls: Option<i64> = conn
+            .query_row(
+                "SELECT calls FROM roboflow_usage WHERE period = ?1",
+                [period],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(calls.unwrap_or(0) as u64)
+    })
+    .await
+}
+
+/// Counts Roboflow calls against the configured daily and monthly budgets.
+/// The counts live in the database, so a restart doesn't hand out a fresh
+/// budget.
+pub struct Quota {
+    db: Database,
+    usage: Mutex<QuotaUsage>,
+}
+
+impl Quota {
+    pub async fn load(db: Database, config: &RoboflowConfig) -> Result<Self, DbError> {
+        let (day, month) = periods();
+        let usage = QuotaUsage {
+            daily_calls: calls(&db, day.clone()).await?,
+            daily_limit: config.daily_quota,
+            monthly_calls: calls(&db, month.clone()).await?,
+            monthly_limit: config.monthly_quota,
+            day,
+            month,
+        };
+        Ok(Quota {
+            db,
+            usage: Mutex::new(usage),
+        })
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        let mut usage = self.usage.lock().unwrap();
+        usage.roll_over();
+        usage.clone()
+    }
+
+    /// Why no more calls may be made, if the budget is used up.
+    pub fn exhausted(&self) -> Option<String> {
+        self.usage().exhausted()
+    }
+
+    /// Spends one call, or refuses once the budget is used up. Counted before
+    /// the request is sent so concurrent frames can't overshoot the budget.
+    pub async fn spend(&self) -> Result<(), DetectorError> {
+        let (day, month) = {
+            let mut usage = self.usage.lock().unwrap();
+            usage.roll_over();
+            if let Some(reason) = usage.exhausted() {
+                return Err(DetectorError::QuotaExceeded(reason));
+            }
+            usage.daily_calls += 1;
+            usage.monthly_calls += 1;
+            (usage.day.clone(), usage.month.clone())
+        };
+
+        let stored = self
+            .db
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                for period in [day, month] {
+                    tx.execute(
+                        "INSERT INTO roboflow_usage (period, calls) VALUES (?1, 1)
+                         ON CONFLICT (period) DO UPDATE SET calls = calls + 1",
+                        params![period],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await;
+        // The in-memory count still holds; only a restart would forget this call.
+        if let Err(err) = stored {
+            warn!(error = %err, "Failed to record Roboflow usage");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,520 @@
+use std::fmt;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::measure::Length;
+
+/// What the angler should do with a fish.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Keep,
+    Release,
+    MustReleaseProtected,
+    /// The species has size limits but the fish couldn't be measured.
+    CheckSize,
+    /// The regulations don't list the class, which may not even be a fish.
+    UnknownSpecies,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Keep => "keep",
+            Verdict::Release => "release",
+            Verdict::MustReleaseProtected => "must_release_protected",
+            Verdict::CheckSize => "check_size",
+            Verdict::UnknownSpecies => "unknown_species",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "keep" => Some(Verdict::Keep),
+            "release" => Some(Verdict::Release),
+            "must_release_protected" => Some(Verdict::MustReleaseProtected),
+            "check_size" => Some(Verdict::CheckSize),
+            "unknown_species" => Some(Verdict::UnknownSpecies),
+            _ => None,
+        }
+    }
+}
+
+/// A verdict and the rule that produced it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Assessment {
+    pub verdict: Verdict,
+    /// Identifier of the triggering rule (`protected`, `closed_season`,
+    /// `min_length`, `max_length`, `slot`), absent when no rule applied. For
+    /// `check_size`, the limit the length is too close to.
+    pub rule: Option<String>,
+    pub reason: String,
+    pub jurisdiction: String,
+}
+
+/// A calendar day without a year, written `MM-DD` in the regulations file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MonthDay {
+    month: u32,
+    day: u32,
+}
+
+impl MonthDay {
+    fn of(date: NaiveDate) -> Self {
+        MonthDay {
+            month: date.month(),
+            day: date.day(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MonthDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let parsed = text.split_once('-').and_then(|(month, day)| {
+            let month: u32 = month.parse().ok()?;
+            let day: u32 = day.parse().ok()?;
+            // 2024 is a leap year, so 02-29 is accepted.
+            NaiveDate::from_ymd_opt(2024, month, day).map(|_| MonthDay { month, day })
+        });
+        parsed.ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid date '{}', expected MM-DD", text))
+        })
+    }
+}
+
+impl fmt::Display for MonthDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+/// Inclusive open period. `close` before `open` means the season spans New Year.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Season {
+    pub open: MonthDay,
+    pub close: MonthDay,
+}
+
+impl Season {
+    fn contains(&self, day: MonthDay) -> bool {
+        if self.open <= self.close {
+            self.open <= day && day <= self.close
+        } else {
+            day >= self.open || day <= self.close
+        }
+    }
+}
+
+/// Protected slot: fish whose length falls inside it must be released.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Slot {
+    pub min_cm: f32,
+    pub max_cm: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpeciesRule {
+    /// Detector class this rule applies to.
+    pub class: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub protected: bool,
+    /// Open seasons; an empty list means open all year.
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+    pub min_length_cm: Option<f32>,
+    pub max_length_cm: Option<f32>,
+    pub slot: Option<Slot>,
+    /// Most that one angler may keep per day.
+    pub daily_limit: Option<u32>,
+    /// Most that one angler may hold at once, counted per trip.
+    pub possession_limit: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Jurisdiction {
+    pub id: String,
+    #[serde(default)]
+    pub species: Vec<SpeciesRule>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Regulations {
+    #[serde(rename = "jurisdiction", default)]
+    pub jurisdictions: Vec<Jurisdiction>,
+}
+
+/// Lower-cases and treats spaces, hyphens and underscores alike, so
+/// "Largemouth Bass" matches `largemouth_bass`.
+fn normalize(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            ' ' | '-' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+impl SpeciesRule {
+    fn matches(&self, class: &str) -> bool {
+        let class = normalize(class);
+        normalize(&self.class) == class || self.aliases.iter().any(|a| normalize(a) == class)
+    }
+
+    /// Every class this rule applies to, normalized as `matches` compares them.
+    pub fn names(&self) -> Vec<String> {
+        std::iter::once(&self.class)
+            .chain(&self.aliases)
+            .map(|name| normalize(name))
+            .collect()
+    }
+
+    /// The first rule that forbids keeping the fish, if any. Size rules are
+    /// skipped when the length is unknown. A length that breaks no limit but is
+    /// within its uncertainty of one gets `CheckSize`, since the fish may well
+    /// be on the wrong side of it.
+    fn violation(
+        &self,
+        length: Option<Length>,
+        date: NaiveDate,
+    ) -> Option<(Verdict, &'static str, String)> {
+        if self.protected {
+            return Some((
+                Verdict::MustReleaseProtected,
+                "protected",
+                "Protected species".to_string(),
+            ));
+        }
+        let today = MonthDay::of(date);
+        if !self.seasons.is_empty() && !self.seasons.iter().any(|season| season.contains(today)) {
+            let seasons: Vec<String> = self
+                .seasons
+                .iter()
+                .map(|season| format!("{} to {}", season.open, season.close))
+                .collect();
+            return Some((
+                Verdict::Release,
+                "closed_season",
+                format!("Season closed; open {}", seasons.join(", ")),
+            ));
+        }
+        let Length {
+            cm: length,
+            uncertainty_cm: margin,
+            ..
+        } = length?;
+        if let Some(min) = self.min_length_cm {
+            if length < min {
+                return Some((
+                    Verdict::Release,
+                    "min_length",
+                    format!("{:.1} cm is under the {:.1} cm minimum", length, min),
+                ));
+            }
+        }
+        if let Some(max) = self.max_length_cm {
+            if length > max {
+                return Some((
+                    Verdict::Release,
+                    "max_length",
+                    format!("{:.1} cm is over the {:.1} cm maximum", length, max),
+                ));
+            }
+        }
+        if let Some(slot) = &self.slot {
+            if (slot.min_cm..=slot.max_cm).contains(&length) {
+                return Some((
+                    Verdict::Release,
+                    "slot",
+                    format!(
+                        "{:.1} cm is inside the {:.1}-{:.1} cm protected slot",
+                        length, slot.min_cm, slot.max_cm
+                    ),
+                ));
+            }
+        }
+
+        let too_close = |limit: f32, what: &str| {
+            format!(
+                "{:.1} ± {:.1} cm is too close to the {:.1} cm {} to be sure; measure it",
+                length, margin, limit, what
+            )
+        };
+        if let Some(min) = self.min_length_cm.filter(|min| length - margin < *min) {
+            return Some((Verdict::CheckSize, "min_length", too_close(min, "minimum")));
+        }
+        if let Some(max) = self.max_length_cm.filter(|max| length + margin > *max) {
+            return Some((Verdict::CheckSize, "max_length", too_close(max, "maximum")));
+        }
+        if let Some(slot) = &self.slot {
+            if length + margin >= slot.min_cm && length - margin <= slot.max_cm {
+                let edge = if length < slot.min_cm {
+                    slot.min_cm
+                } else {
+                    slot.max_cm
+                };
+                return Some((Verdict::CheckSize, "slot", too_close(edge, "slot edge")));
+            }
+        }
+        None
+    }
+}
+
+impl Regulations {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&contents).map_err(|err| format!("Invalid {}: {}", path.display(), err))
+    }
+
+    pub fn jurisdiction(&self, id: &str) -> Option<&Jurisdiction> {
+        self.jurisdictions.iter().find(|j| j.id == id)
+    }
+
+    pub fn species(&self, jurisdiction: &str, class: &str) -> Option<&SpeciesRule> {
+        self.jurisdiction(jurisdiction)?
+            .species
+            .iter()
+            .find(|rule| rule.matches(class))
+    }
+
+    /// Decides whether a fish of `class` may be kept in `jurisdiction` on `date`.
+    /// Only `Keep` says it may; `CheckSize` and `UnknownSpecies` leave the
+    /// decision to the angler and never count as a keep.
+    pub fn assess(
+        &self,
+        jurisdiction: &str,
+        class: &str,
+        length: Option<Length>,
+        date: NaiveDate,
+    ) -> Assessment {
+        let (verdict, rule, reason) = match self.species(jurisdiction, class) {
+            Some(species) => match species.violation(length, date) {
+                Some((verdict, rule, reason)) => (verdict, Some(rule.to_string()), reason),
+                None if length.is_none()
+                    && (species.min_length_cm.is_some()
+                        || species.max_length_cm.is_some()
+                        || species.slot.is_some()) =>
+                {
+                    (
+                        Verdict::CheckSize,
+                        None,
+                        "In season; measure it before keeping".to_string(),
+                    )
+                }
+                None => (Verdict::Keep, None, "Within all limits".to_string()),
+            },
+            None => (
+                Verdict::UnknownSpecies,
+                None,
+                "No regulations listed for this species; check the local rules before keeping"
+                    .to_string(),
+            ),
+        };
+        Assessment {
+            verdict,
+            rule,
+            reason,
+            jurisdiction: jurisdiction.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{Regulations, Verdict};
+    use crate::measure::Length;
+
+    const RULES: &str = r#"
+        [[jurisdiction]]
+        id = "test"
+
+        [[jurisdiction.species]]
+        class = "largemouth_bass"
+        aliases = ["bucketmouth"]
+        min_length_cm = 30.0
+        slot = { min_cm = 40.0, max_cm = 50.0 }
+
+        [[jurisdiction.species]]
+        class = "walleye"
+        seasons = [{ open = "12-01", close = "02-28" }]
+        min_length_cm = 35.0
+
+        [[jurisdiction.species]]
+        class = "sturgeon"
+        protected = true
+        seasons = [{ open = "06-01", close = "06-30" }]
+        min_length_cm = 100.0
+
+        [[jurisdiction.species]]
+        class = "bluegill"
+    "#;
+
+    fn regulations() -> Regulations {
+        toml::from_str(RULES).unwrap()
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn measured(cm: f32, uncertainty_cm: f32) -> Length {
+        Length {
+            cm,
+            inches: cm / 2.54,
+            uncertainty_cm,
+        }
+    }
+
+    /// Assesses an exactly measured fish, or an unmeasured one.
+    fn verdict(class: &str, length_cm: Option<f32>, on: NaiveDate) -> (Verdict, Option<String>) {
+        let length = length_cm.map(|cm| measured(cm, 0.0));
+        let assessment = regulations().assess("test", class, length, on);
+        (assessment.verdict, assessment.rule)
+    }
+
+    #[test]
+    fn season_spanning_new_year() {
+        for open in [date(12, 1), date(1, 15), date(2, 28)] {
+            assert_eq!(verdict("walleye", Some(40.0), open).0, Verdict::Keep);
+        }
+        for closed in [date(11, 30), date(3, 1), date(7, 1)] {
+            assert_eq!(
+                verdict("walleye", Some(40.0), closed),
+                (Verdict::Release, Some("closed_season".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn closed_season_comes_before_size() {
+        assert_eq!(
+            verdict("walleye", Some(20.0), date(7, 1)).1.as_deref(),
+            Some("closed_season")
+        );
+        assert_eq!(
+            verdict("walleye", Some(20.0), date(1, 1)).1.as_deref(),
+            Some("min_length")
+        );
+    }
+
+    #[test]
+    fn slot_is_inclusive() {
+        let today = date(6, 1);
+        for inside in [40.0, 45.0, 50.0] {
+            assert_eq!(
+                verdict("largemouth_bass", Some(inside), today),
+                (Verdict::Release, Some("slot".to_string()))
+            );
+        }
+        for outside in [39.9, 50.1] {
+            assert_eq!(
+                verdict("largemouth_bass", Some(outside), today).0,
+                Verdict::Keep
+            );
+        }
+        assert_eq!(
+            verdict("largemouth_bass", Some(29.9), today).1.as_deref(),
+            Some("min_length")
+        );
+    }
+
+    #[test]
+    fn limit_within_uncertainty_needs_a_size_check() {
+        let assess = |cm, uncertainty_cm| {
+            let assessment = regulations().assess(
+                "test",
+                "largemouth_bass",
+                Some(measured(cm, uncertainty_cm)),
+                date(6, 1),
+            );
+            (assessment.verdict, assessment.rule)
+        };
+        assert_eq!(
+            assess(30.2, 2.5),
+            (Verdict::CheckSize, Some("min_length".to_string()))
+        );
+        assert_eq!(
+            assess(38.0, 2.5),
+            (Verdict::CheckSize, Some("slot".to_string()))
+        );
+        assert_eq!(
+            assess(51.0, 2.5),
+            (Verdict::CheckSize, Some("slot".to_string()))
+        );
+        assert_eq!(assess(35.0, 2.5).0, Verdict::Keep);
+        // Measured on the wrong side of a limit is a release however unsure.
+        assert_eq!(
+            assess(29.0, 2.5),
+            (Verdict::Release, Some("min_length".to_string()))
+        );
+        assert_eq!(
+            assess(45.0, 10.0),
+            (Verdict::Release, Some("slot".to_string()))
+        );
+    }
+
+    #[test]
+    fn protected_wins_over_everything() {
+        // Out of season and under the minimum, but protection is what applies.
+        assert_eq!(
+            verdict("sturgeon", Some(50.0), date(1, 1)),
+            (Verdict::MustReleaseProtected, Some("protected".to_string()))
+        );
+        assert_eq!(
+            verdict("sturgeon", None, date(6, 15)).0,
+            Verdict::MustReleaseProtected
+        );
+    }
+
+    #[test]
+    fn unmeasured_fish_needs_a_size_check() {
+        assert_eq!(
+            verdict("largemouth_bass", None, date(6, 1)),
+            (Verdict::CheckSize, None)
+        );
+        // Without size limits there is nothing to measure against.
+        assert_eq!(verdict("bluegill", None, date(6, 1)).0, Verdict::Keep);
+    }
+
+    #[test]
+    fn matches_aliases_and_spelling() {
+        let today = date(6, 1);
+        for class in [
+            "largemouth_bass",
+            "Largemouth Bass",
+            "LARGEMOUTH-BASS",
+            "Bucketmouth",
+        ] {
+            assert_eq!(
+                verdict(class, Some(41.0), today).1.as_deref(),
+                Some("slot"),
+                "{}",
+                class
+            );
+        }
+        assert_eq!(
+            verdict("jellyfish", Some(41.0), today),
+            (Verdict::UnknownSpecies, None)
+        );
+        // Rules only apply in their own jurisdiction.
+        assert_eq!(
+            regulations()
+                .assess("elsewhere", "bluegill", None, today)
+                .verdict,
+            Verdict::UnknownSpecies
+        );
+    }
+}
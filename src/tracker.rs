@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::TrackingConfig;
+use crate::detector::{DetectionResult, Rect};
+
+/// Classes below this smoothed score are forgotten.
+const MIN_SCORE: f32 = 0.01;
+/// How many classes of the distribution are reported.
+const REPORTED_CLASSES: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClassScore {
+    pub class: String,
+    pub score: f32,
+}
+
+/// What the tracker knows about the object behind a detection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrackInfo {
+    /// Stable for as long as the object stays in view on this connection.
+    pub id: u64,
+    /// Frames the object has been seen in.
+    pub frames: u32,
+    /// Most likely class after smoothing.
+    pub class: String,
+    pub confidence: f32,
+    /// Smoothed confidence per class, highest first.
+    pub classes: Vec<ClassScore>,
+}
+
+struct Track {
+    id: u64,
+    /// `None` for the whole-frame track fed by classifiers without boxes.
+    rect: Option<Rect>,
+    /// Exponential moving average of each class's confidence.
+    scores: HashMap<String, f32>,
+    frames: u32,
+    missed: u32,
+}
+
+impl Track {
+    fn observe(&mut self, rect: Option<Rect>, observed: &[(String, f32)], smoothing: f32) {
+        for score in self.scores.values_mut() {
+            *score *= 1.0 - smoothing;
+        }
+        for (class, confidence) in observed {
+            *self.scores.entry(class.clone()).or_default() += smoothing * confidence;
+        }
+        self.scores.retain(|_, score| *score >= MIN_SCORE);
+        self.rect = rect;
+        self.frames += 1;
+        self.missed = 0;
+    }
+
+    fn info(&self) -> TrackInfo {
+        let mut classes: Vec<ClassScore> = self
+            .scores
+            .iter()
+            .map(|(class, score)| ClassScore {
+                class: class.clone(),
+                score: *score,
+            })
+            .collect();
+        classes.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.class.cmp(&b.class)));
+        classes.truncate(REPORTED_CLASSES);
+        let (class, confidence) = classes
+            .first()
+            .map(|top| (top.class.clone(), top.score))
+            .unwrap_or_default();
+        TrackInfo {
+            id: self.id,
+            frames: self.frames,
+            class,
+            confidence,
+            classes,
+        }
+    }
+}
+
+/// Detections from one frame that belong to the same object: a single boxed
+/// detection, or every box-less detection (a classifier's top guesses for the
+/// whole frame).
+struct Observation {
+    rect: Option<Rect>,
+    indices: Vec<usize>,
+}
+
+/// Follows objects across the frames of one WebSocket connection, matching
+/// boxes by IoU and smoothing each object's class scores over time.
+pub struct Tracker {
+    tracks: Vec<Track>,
+    next_id: u64,
+    iou_threshold: f32,
+    smoothing: f32,
+    max_missed_frames: u32,
+    /// Sequence number of the newest frame applied so far.
+    newest_frame: Option<u64>,
+}
+
+impl Tracker {
+    pub fn new(config: &TrackingConfig) -> Self {
+        Tracker {
+            tracks: Vec::new(),
+            next_id: 0,
+            iou_threshold: config.iou_threshold,
+            smoothing: config.smoothing,
+            max_missed_frames: config.max_missed_frames,
+            newest_frame: None,
+        }
+    }
+
+    pub fn newest_frame(&self) -> Option<u64> {
+        self.newest_frame
+    }
+
+    /// Matches this frame's detections to existing tracks, starting new tracks
+    /// for the rest, and fills in each detection's `track`. Frames can finish
+    /// out of order when several are analysed at once; one older than a frame
+    /// already applied is left untouched, so it can't age tracks or smooth in
+    /// stale scores.
+    pub fn update(&mut self, sequence: u64, detections: &mut [DetectionResult]) {
+        if self.newest_frame.is_some_and(|newest| newest > sequence) {
+            return;
+        }
+        self.newest_frame = Some(sequence);
+        let mut observations: Vec<Observation> = Vec::new();
+        let mut frame_wide = Vec::new();
+        for (index, detection) in detections.iter().enumerate() {
+            match detection.bounding_box {
+                Some(bounding_box) => observations.push(Observation {
+                    rect: Some(bounding_box.pixel),
+                    indices: vec![index],
+                }),
+                None => frame_wide.push(index),
+            }
+        }
+        if !frame_wide.is_empty() {
+            observations.push(Observation {
+                rect: None,
+                indices: frame_wide,
+            });
+        }
+
+        // Greedy assignment, best overlap first. A frame rarely holds more than a
+        // handful of fish, so this is as good as an optimal matching in practice.
+        let mut candidates = Vec::new();
+        for (o, observation) in observations.iter().enumerate() {
+            for (t, track) in self.tracks.iter().enumerate() {
+                let overlap = match (&observation.rect, &track.rect) {
+                    (Some(a), Some(b)) => a.iou(b),
+                    (None, None) => 1.0,
+                    _ => 0.0,
+                };
+                if overlap >= self.iou_threshold {
+                    candidates.push((overlap, o, t));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut assigned: Vec<Option<usize>> = vec![None; observations.len()];
+        let mut matched = vec![false; self.tracks.len()];
+        for (_, o, t) in candidates {
+            if assigned[o].is_none() && !matched[t] {
+                assigned[o] = Some(t);
+                matched[t] = true;
+            }
+        }
+
+        for track in self
+            .tracks
+            .iter_mut()
+            .zip(&matched)
+            .filter(|(_, matched)| !**matched)
+            .map(|(track, _)| track)
+        {
+            track.missed += 1;
+        }
+
+        for (observation, assigned) in observations.iter().zip(assigned) {
+            let observed: Vec<(String, f32)> = observation
+                .indices
+                .iter()
+                .map(|&i| (detections[i].class.clone(), detections[i].confidence))
+                .collect();
+            let t = match assigned {
+                Some(t) => t,
+                None => {
+                    self.tracks.push(Track {
+                        id: self.next_id,
+                        rect: observation.rect,
+                        scores: HashMap::new(),
+                        frames: 0,
+                        missed: 0,
+                    });
+                    self.next_id += 1;
+                    self.tracks.len() - 1
+                }
+            };
+            // A new track takes the first frame's scores as they are.
+            let smoothing = if self.tracks[t].frames == 0 {
+                1.0
+            } else {
+                self.smoothing
+            };
+            self.tracks[t].observe(observation.rect, &observed, smoothing);
+            let info = self.tracks[t].info();
+            for &i in &observation.indices {
+                detections[i].track = Some(info.clone());
+            }
+        }
+
+        let max_missed = self.max_missed_frames;
+        self.tracks.retain(|track| track.missed <= max_missed);
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, types::Type, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::api::ApiError;
+use crate::db::{Database, DbError};
+use crate::AppState;
+
+/// Who is fishing and on which outing. Sent as `angler_id`/`trip_id` query
+/// parameters on `/ws` and as fields on `/detect` and `/catches`.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub struct TripContext {
+    pub angler_id: Option<i64>,
+    pub trip_id: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Angler {
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Trip {
+    pub id: i64,
+    pub name: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_time(row: &Row, column: &str, index: usize) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    let Some(value) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+    DateTime::parse_from_rfc3339(&value)
+        .map(|time| Some(time.with_timezone(&Utc)))
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err)))
+}
+
+fn angler_from_row(row: &Row) -> rusqlite::Result<Angler> {
+    Ok(Angler {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        created_at: parse_time(row, "created_at", 2)?.unwrap_or_default(),
+    })
+}
+
+fn trip_from_row(row: &Row) -> rusqlite::Result<Trip> {
+    Ok(Trip {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        started_at: parse_time(row, "started_at", 2)?.unwrap_or_default(),
+        ended_at: parse_time(row, "ended_at", 3)?,
+    })
+}
+
+pub async fn find_angler(db: &Database, id: i64) -> Result<Option<Angler>, DbError> {
+    db.call(move |conn| {
+        Ok(conn
+            .query_row("SELECT * FROM anglers WHERE id = ?1", [id], angler_from_row)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn find_trip(db: &Database, id: i64) -> Result<Option<Trip>, DbError> {
+    db.call(move |conn| {
+        Ok(conn
+            .query_row("SELECT * FROM trips WHERE id = ?1", [id], trip_from_row)
+            .optional()?)
+    })
+    .await
+}
+
+/// Rejects ids that don't refer to an existing angler or trip, and trips that
+/// have ended: frames and catches after the end would count towards a bag the
+/// angler has already taken home.
+pub async fn check_context(db: &Database, context: TripContext) -> Result<(), ApiError> {
+    if let Some(id) = context.angler_id {
+        if find_angler(db, id).await?.is_none() {
+            return Err(ApiError::not_found(format!("no angler with id {}", id)));
+        }
+    }
+    if let Some(id) = context.trip_id {
+        let Some(trip) = find_trip(db, id).await? else {
+            return Err(ApiError::not_found(format!("no trip with id {}", id)));
+        };
+        if let Some(ended_at) = trip.ended_at {
+            return Err(ApiError::conflict(format!(
+                "trip {} ended at {}",
+                id,
+                ended_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct NewAngler {
+    name: String,
+}
+
+/// `POST /anglers`
+pub async fn create_angler(
+    State(state): State<AppState>,
+    Json(new_angler): Json<NewAngler>,
+) -> Result<Json<Angler>, ApiError> {
+    let name = new_angler.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("angler name can't be empty"));
+    }
+    let angler = state
+        .db
+        .call(move |conn| {
+            conn.execute(
+                "INSERT INTO anglers (name, created_at) VALUES (?1, ?2)",
+                params![name, now()],
+            )?;
+            let id = conn.last_insert_rowid();
+            Ok(conn.query_row("SELECT * FROM anglers WHERE id = ?1", [id], angler_from_row)?)
+        })
+        .await?;
+    Ok(Json(angler))
+}
+
+/// `GET /anglers`
+pub async fn list_anglers(State(state): State<AppState>) -> Result<Json<Vec<Angler>>, ApiError> {
+    let anglers = state
+        .db
+        .call(|conn| {
+            let mut statement = conn.prepare("SELECT * FROM anglers ORDER BY id")?;
+            let anglers = statement
+                .query_map([], angler_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(anglers)
+        })
+        .await?;
+    Ok(Json(anglers))
+}
+
+#[derive(Deserialize)]
+pub struct NewTrip {
+    name: Option<String>,
+}
+
+/// `POST /trips`
+pub async fn create_trip(
+    State(state): State<AppState>,
+    Json(new_trip): Json<NewTrip>,
+) -> Result<Json<Trip>, ApiError> {
+    let trip = state
+        .db
+        .call(move |conn| {
+            conn.execute(
+                "INSERT INTO trips (name, started_at) VALUES (?1, ?2)",
+                params![new_trip.name, now()],
+            )?;
+            let id = conn.last_insert_rowid();
+            Ok(conn.query_row("SELECT * FROM trips WHERE id = ?1", [id], trip_from_row)?)
+        })
+        .await?;
+    Ok(Json(trip))
+}
+
+/// `POST /trips/:id/end`
+pub async fn end_trip(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Trip>, ApiError> {
+    let trip = state
+        .db
+        .call(move |conn| {
+            conn.execute(
+                "UPDATE trips SET ended_at = ?1 WHERE id = ?2 AND ended_at IS NULL",
+                params![now(), id],
+            )?;
+            Ok(conn
+                .query_row("SELECT * FROM trips WHERE id = ?1", [id], trip_from_row)
+                .optional()?)
+        })
+        .await?;
+    trip.map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("no trip with id {}", id)))
+}
+
+#[derive(Serialize)]
+pub struct TripSummary {
+    #[serde(flatten)]
+    trip: Trip,
+    /// Fish kept on this trip, by angler id and then species.
+    kept: BTreeMap<i64, BTreeMap<String, u32>>,
+}
+
+/// `GET /trips/:id`
+pub async fn get_trip(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<TripSummary>, ApiError> {
+    let Some(trip) = find_trip(&state.db, id).await? else {
+        return Err(ApiError::not_found(format!("no trip with id {}", id)));
+    };
+    let kept = state
+        .db
+        .call(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT angler_id, species, COUNT(*) FROM catches \
+                 WHERE trip_id = ?1 AND verdict = 'keep' AND angler_id IS NOT NULL \
+                 GROUP BY angler_id, species",
+            )?;
+            let mut kept: BTreeMap<i64, BTreeMap<String, u32>> = BTreeMap::new();
+            let rows = statement.query_map([id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u32>(2)?,
+                ))
+            })?;
+            for row in rows {
+                let (angler_id, species, count) = row?;
+                kept.entry(angler_id).or_default().insert(species, count);
+            }
+            Ok(kept)
+        })
+        .await?;
+    Ok(Json(TripSummary { trip, kept }))
+}
@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::{
+    extract::{ConnectInfo, FromRequest, Multipart, Request, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    Extension, Json,
+};
+use serde::Deserialize;
+use tracing::{info_span, warn, Instrument};
+
+use crate::auth::Client;
+use crate::detector::DetectorError;
+use crate::limits;
+use crate::location::Location;
+use crate::pipeline::{self, FrameContext};
+use crate::protocol::Response;
+use crate::trips::{self, TripContext};
+use crate::AppState;
+
+// Used when the caller doesn't send a request id of their own.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+/// JSON alternative to a multipart upload.
+#[derive(Deserialize)]
+struct DetectRequest {
+    /// Base64 image, optionally as a `data:` URL.
+    image: String,
+    request_id: Option<String>,
+    #[serde(flatten)]
+    context: TripContext,
+    location: Option<Location>,
+}
+
+struct Upload {
+    image: Vec<u8>,
+    request_id: Option<String>,
+    context: TripContext,
+    location: Option<Location>,
+}
+
+fn status_for(err: &DetectorError) -> StatusCode {
+    match err {
+        DetectorError::InvalidImage(_) | DetectorError::InvalidRequest(_) => {
+            StatusCode::BAD_REQUEST
+        }
+        DetectorError::QuotaExceeded(_) | DetectorError::RateLimited(_) => {
+            StatusCode::TOO_MANY_REQUESTS
+        }
+        DetectorError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        DetectorError::CircuitOpen | DetectorError::Model(_) => StatusCode::SERVICE_UNAVAILABLE,
+        DetectorError::Unauthorized(_)
+        | DetectorError::Upstream { .. }
+        | DetectorError::Network(_)
+        | DetectorError::Parse(_) => StatusCode::BAD_GATEWAY,
+    }
+}
+
+async fn read_multipart(mut multipart: Multipart) -> Result<Upload, DetectorError> {
+    let invalid = |err: axum::extract::multipart::MultipartError| {
+        DetectorError::InvalidImage(err.body_text())
+    };
+
+    let mut image = None;
+    let mut request_id = None;
+    let mut context = TripContext::default();
+    let mut location = None;
+    while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+        let name = field.name().map(str::to_owned);
+        match name.as_deref() {
+            Some("request_id") => request_id = Some(field.text().await.map_err(invalid)?),
+            Some(name @ ("angler_id" | "trip_id")) => {
+                let text = field.text().await.map_err(invalid)?;
+                let id = text.trim().parse().map_err(|_| {
+                    DetectorError::InvalidRequest(format!("{} must be an integer", name))
+                })?;
+                if name == "angler_id" {
+                    context.angler_id = Some(id);
+                } else {
+                    context.trip_id = Some(id);
+                }
+            }
+            // `{"latitude": .., "longitude": ..}`, the same shape as the JSON body
+            Some("location") => {
+                let text = field.text().await.map_err(invalid)?;
+                location = Some(serde_json::from_str(&text).map_err(|err| {
+                    DetectorError::InvalidRequest(format!("invalid location: {}", err))
+                })?);
+            }
+            Some("image") => image = Some(field.bytes().await.map_err(invalid)?.to_vec()),
+            // Accept the first file under any name, which is what most clients send.
+            _ if field.file_name().is_some() && image.is_none() => {
+                image = Some(field.bytes().await.map_err(invalid)?.to_vec())
+            }
+            _ => {}
+        }
+    }
+
+    match image {
+        Some(image) => Ok(Upload {
+            image,
+            request_id,
+            context,
+            location,
+        }),
+        None => Err(DetectorError::InvalidImage(
+            "multipart body has no image field".to_string(),
+        )),
+    }
+}
+
+async fn read_upload(state: &AppState, request: Request) -> Result<Upload, DetectorError> {
+    let is_multipart = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    if is_multipart {
+        let multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(|err| DetectorError::InvalidImage(err.body_text()))?;
+        read_multipart(multipart).await
+    } else {
+        let Json(body) = Json::<DetectRequest>::from_request(request, state)
+            .await
+            .map_err(|err| DetectorError::InvalidImage(err.body_text()))?;
+        Ok(Upload {
+            image: pipeline::decode_base64(&body.image)?,
+            request_id: body.request_id,
+            context: body.context,
+            location: body.location,
+        })
+    }
+}
+
+/// `POST /detect`: identifies a single image sent either as `multipart/form-data`
+/// (an `image` file field) or as JSON (`{"image": "<base64>"}`), answering with
+/// the same envelope the WebSocket uses. Both forms take an optional `location`.
+pub async fn detect(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    client: Option<Extension<Client>>,
+    request: Request,
+) -> (StatusCode, Json<Response>) {
+    let upload = read_upload(&state, request).await;
+    let request_id = upload
+        .as_ref()
+        .ok()
+        .and_then(|upload| upload.request_id.clone())
+        .unwrap_or_else(|| format!("http-{}", NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)));
+
+    let upload = match upload {
+        Ok(upload) => match trips::check_context(&state.db, upload.context).await {
+            Ok(()) => Ok(upload),
+            Err(err) => {
+                let response = Response::failure(request_id, err.code, err.message);
+                return (err.status, Json(response));
+            }
+        },
+        Err(err) => Err(err),
+    };
+    let result = match upload {
+        Ok(upload) => match upload.location.map(|location| location.check()) {
+            Some(Err(reason)) => Err(DetectorError::InvalidRequest(reason)),
+            _ => {
+                state.metrics.frame_received();
+                let context = FrameContext {
+                    trip: upload.context,
+                    location: upload.location,
+                    filter: None,
+                    client: limits::client_id(client.as_ref(), peer),
+                };
+                pipeline::run(&state, upload.image, &context, None)
+                    .instrument(info_span!("frame", %request_id))
+                    .await
+            }
+        },
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(analysis) => (StatusCode::OK, Json(Response::result(request_id, analysis))),
+        Err(err) => {
+            warn!(
+                %request_id,
+                backend = state.detector.name(),
+                error = %err,
+                "Detector failed"
+            );
+            (status_for(&err), Json(Response::error(request_id, &err)))
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Query, State,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use futures::{sink::SinkExt, stream::StreamExt};
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{mpsc, Notify, Semaphore};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::api::ApiError;
+use crate::auth::Client;
+use crate::catches::{self, Catch};
+use crate::detector::{DetectionResult, DetectorError};
+use crate::filter::FilterUpdate;
+use crate::limits;
+use crate::location::Location;
+use crate::pipeline::{self, FrameContext};
+use crate::protocol::{
+    ClientMessage, Response, Topic, CAPABILITIES, CLIENT_MESSAGE_TYPES, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION,
+};
+use crate::tracker::Tracker;
+use crate::trips::{self, TripContext};
+use crate::AppState;
+
+/// Messages waiting to be written to the socket.
+const OUTGOING_CAPACITY: usize = 32;
+
+/// Tells connections apart in the logs.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The most recent frame, kept so `confirm_catch` can refer back to it.
+#[derive(Clone)]
+struct LastFrame {
+    image: Vec<u8>,
+    detections: Vec<DetectionResult>,
+}
+
+/// A frame waiting for an inference slot, with the settings it arrived under.
+struct PendingFrame {
+    sequence: u64,
+    request_id: String,
+    image: Vec<u8>,
+    context: FrameContext,
+}
+
+/// State shared between the task reading the socket and the inferences it
+/// starts.
+///
+/// Frames don't queue: only the newest one waits for an inference slot, and a
+/// frame that arrives while another is waiting replaces it. Cameras send faster
+/// than Roboflow answers, so a queue would only make results lag ever further
+/// behind what the angler is pointing at.
+struct Connection {
+    state: AppState,
+    context: Mutex<FrameContext>,
+    tracker: Option<Mutex<Tracker>>,
+    last_frame: Mutex<Option<LastFrame>>,
+    pending: Mutex<Option<PendingFrame>>,
+    frame_ready: Notify,
+    inferences: Arc<Semaphore>,
+    next_sequence: AtomicU64,
+    /// Sequence number of the newest frame whose result has been sent.
+    newest_result: Mutex<Option<u64>>,
+    dropped_frames: AtomicU64,
+    outgoing: mpsc::Sender<Message>,
+}
+
+/// What only the reading task needs.
+struct Session {
+    connection: Arc<Connection>,
+    // Identifies responses to messages that didn't bring a request id.
+    next_request_id: u64,
+    topics: HashSet<Topic>,
+}
+
+/// Parses a typed message, telling unknown types apart from malformed ones.
+/// Errors carry the message's request id when it could be read.
+fn parse(text: &str) -> Result<ClientMessage, (Option<String>, &'static str, String)> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|err| (None, "invalid_request", err.to_string()))?;
+    let request_id = value
+        .get("request_id")
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+    match value.get("type").and_then(Value::as_str) {
+        None => Err((
+            request_id,
+            "invalid_request",
+            "message has no type".to_string(),
+        )),
+        Some(kind) if !CLIENT_MESSAGE_TYPES.contains(&kind) => Err((
+            request_id,
+            "unknown_message",
+            format!("unknown message type '{}'", kind),
+        )),
+        Some(_) => serde_json::from_value(value)
+            .map_err(|err| (request_id, "invalid_request", err.to_string())),
+    }
+}
+
+fn api_error(request_id: String, err: ApiError) -> Response {
+    Response::failure(request_id, err.code, err.message)
+}
+
+impl Connection {
+    /// Queues a response for the socket. Fails once the socket has gone.
+    async fn send(&self, response: &Response) -> bool {
+        match serde_json::to_string(response) {
+            Ok(json) => self.outgoing.send(json.into()).await.is_ok(),
+            Err(_) => true,
+        }
+    }
+
+    fn dropped(&self, request_id: String) -> Response {
+        debug!(%request_id, "Frame dropped");
+        self.state.metrics.frame_dropped();
+        let dropped_frames = self.dropped_frames.fetch_add(1, Ordering::Relaxed) + 1;
+        Response::Dropped {
+            version: PROTOCOL_VERSION,
+            request_id,
+            dropped_frames,
+        }
+    }
+
+    /// Makes `image` the next frame to analyse, returning the notice for the
+    /// frame it displaced, if any.
+    fn submit(&self, request_id: String, image: Vec<u8>) -> Option<Response> {
+        self.state.metrics.frame_received();
+        let frame = PendingFrame {
+            sequence: self.next_sequence.fetch_add(1, Ordering::Relaxed),
+            request_id,
+            image,
+            context: self.context.lock().unwrap().clone(),
+        };
+        let replaced = self.pending.lock().unwrap().replace(frame);
+        self.frame_ready.notify_one();
+        replaced.map(|stale| self.dropped(stale.request_id))
+    }
+
+    /// Starts an inference for the pending frame whenever a slot is free.
+    async fn run_frames(self: Arc<Self>) {
+        loop {
+            let Ok(permit) = self.inferences.clone().acquire_owned().await else {
+                return;
+            };
+            // Taken only once a slot is free, so it's the newest frame by then.
+            let frame = loop {
+                if let Some(frame) = self.pending.lock().unwrap().take() {
+                    break frame;
+                }
+                self.frame_ready.notified().await;
+            };
+            let connection = self.clone();
+            let span =
+                info_span!("frame", request_id = %frame.request_id, sequence = frame.sequence);
+            tokio::spawn(
+                async move {
+                    let response = connection.analyse(frame).await;
+                    drop(permit);
+                    connection.send(&response).await;
+                }
+                .instrument(span),
+            );
+        }
+    }
+
+    async fn analyse(&self, frame: PendingFrame) -> Response {
+        let result = pipeline::run(
+            &self.state,
+            frame.image.clone(),
+            &frame.context,
+            self.tracker
+                .as_ref()
+                .map(|tracker| (tracker, frame.sequence)),
+        )
+        .await;
+        let analysis = match result {
+            Ok(analysis) => analysis,
+            Err(err) => {
+                warn!(backend = self.state.detector.name(), error = %err, "Detector failed");
+                return Response::error(frame.request_id, &err);
+            }
+        };
+
+        // With several inferences in flight a later frame can finish first, and
+        // then this result is already out of date. A later frame that has only
+        // got as far as the tracker counts too, since this one's tracks are
+        // already behind it.
+        {
+            let tracked = self
+                .tracker
+                .as_ref()
+                .and_then(|tracker| tracker.lock().unwrap().newest_frame());
+            let mut newest = self.newest_result.lock().unwrap();
+            if newest
+                .max(tracked)
+                .is_some_and(|newest| newest > frame.sequence)
+            {
+                drop(newest);
+                return self.dropped(frame.request_id);
+            }
+            *newest = Some(frame.sequence);
+        }
+        *self.last_frame.lock().unwrap() = Some(LastFrame {
+            image: frame.image,
+            detections: analysis.detections.clone(),
+        });
+        Response::result(frame.request_id, analysis)
+    }
+
+    async fn configure(
+        &self,
+        location: Option<Location>,
+        filter: Option<FilterUpdate>,
+        angler_id: Option<i64>,
+        trip_id: Option<i64>,
+    ) -> Result<FrameContext, ApiError> {
+        if let Some(location) = &location {
+            location.check().map_err(ApiError::bad_request)?;
+        }
+        let current = self.context.lock().unwrap().clone();
+        let filter = match filter {
+            Some(update) => {
+                let settings = current.filter.as_ref().unwrap_or(&self.state.config.filter);
+                Some(update.apply(settings).map_err(ApiError::bad_request)?)
+            }
+            None => None,
+        };
+        let trip = TripContext {
+            angler_id: angler_id.or(current.trip.angler_id),
+            trip_id: trip_id.or(current.trip.trip_id),
+        };
+        trips::check_context(&self.state.db, trip).await?;
+
+        // Only change anything once everything has been checked.
+        let mut context = self.context.lock().unwrap();
+        context.trip = trip;
+        if location.is_some() {
+            context.location = location;
+        }
+        if filter.is_some() {
+            context.filter = filter;
+        }
+        Ok(context.clone())
+    }
+
+    async fn confirm_catch(
+        &self,
+        index: Option<usize>,
+        track_id: Option<u64>,
+        caught_at: Option<DateTime<Utc>>,
+    ) -> Result<Catch, ApiError> {
+        let frame = self
+            .last_frame
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| ApiError::bad_request("no frame has been analysed yet"))?;
+        let detection = match track_id {
+            Some(track_id) => frame
+                .detections
+                .iter()
+                .find(|d| d.track.as_ref().is_some_and(|track| track.id == track_id)),
+            None => frame.detections.get(index.unwrap_or(0)),
+        }
+        .ok_or_else(|| ApiError::not_found("no such detection in the last frame"))?;
+        let context = self.context.lock().unwrap().clone();
+        catches::confirm(
+            &self.state,
+            detection,
+            context.trip,
+            context.location,
+            Some(frame.image.clone()),
+            caught_at.unwrap_or_else(Utc::now),
+        )
+        .await
+    }
+}
+
+impl Session {
+    fn request_id(&mut self, given: Option<String>) -> String {
+        given.unwrap_or_else(|| {
+            let id = self.next_request_id.to_string();
+            self.next_request_id += 1;
+            id
+        })
+    }
+
+    /// Hands a frame to the inference loop. Only errors and dropped-frame
+    /// notices are answered straight away; results follow when ready.
+    fn frame(
+        &mut self,
+        request_id: String,
+        image: Result<Vec<u8>, DetectorError>,
+    ) -> Option<Response> {
+        match image {
+            Ok(image) => self.connection.submit(request_id, image),
+            Err(err) => Some(Response::error(request_id, &err)),
+        }
+    }
+
+    async fn handle(&mut self, message: ClientMessage) -> Option<Response> {
+        let connection = self.connection.clone();
+        let response = match message {
+            ClientMessage::Hello {
+                request_id,
+                versions,
+                capabilities,
+                client,
+            } => {
+                let request_id = self.request_id(request_id);
+                let offered = if versions.is_empty() {
+                    vec![PROTOCOL_VERSION]
+                } else {
+                    versions
+                };
+                let version = offered
+                    .into_iter()
+                    .filter(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v))
+                    .max();
+                let Some(version) = version else {
+                    return Some(Response::failure(
+                        request_id,
+                        "unsupported_version",
+                        format!(
+                            "server speaks protocol versions {} to {}",
+                            MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+                        ),
+                    ));
+                };
+                info!(
+                    client = client.as_deref().unwrap_or("unnamed"),
+                    version,
+                    capabilities = %capabilities.join(", "),
+                    "WebSocket client said hello"
+                );
+                Response::Hello {
+                    version,
+                    request_id,
+                    server: format!("ethicalfish-pi {}", env!("CARGO_PKG_VERSION")),
+                    capabilities: CAPABILITIES,
+                }
+            }
+            ClientMessage::Frame {
+                request_id,
+                image,
+                location,
+            } => {
+                let request_id = self.request_id(request_id);
+                if let Some(location) = location {
+                    if let Err(reason) = location.check() {
+                        let err = DetectorError::InvalidRequest(reason);
+                        return Some(Response::error(request_id, &err));
+                    }
+                    connection.context.lock().unwrap().location = Some(location);
+                }
+                return self.frame(request_id, pipeline::decode_base64(&image));
+            }
+            ClientMessage::Configure {
+                request_id,
+                location,
+                filter,
+                angler_id,
+                trip_id,
+            } => {
+                let request_id = self.request_id(request_id);
+                match connection
+                    .configure(location, filter, angler_id, trip_id)
+                    .await
+                {
+                    Ok(context) => Response::Configured {
+                        version: PROTOCOL_VERSION,
+                        request_id,
+                        angler_id: context.trip.angler_id,
+                        trip_id: context.trip.trip_id,
+                        location: context.location,
+                        filter: context
+                            .filter
+                            .unwrap_or_else(|| connection.state.config.filter.clone()),
+                    },
+                    Err(err) => api_error(request_id, err),
+                }
+            }
+            ClientMessage::ConfirmCatch {
+                request_id,
+                index,
+                track_id,
+                caught_at,
+            } => {
+                let request_id = self.request_id(request_id);
+                match connection.confirm_catch(index, track_id, caught_at).await {
+                    Ok(catch) => Response::Catch {
+                        version: PROTOCOL_VERSION,
+                        request_id,
+                        catch,
+                    },
+                    Err(err) => api_error(request_id, err),
+                }
+            }
+            ClientMessage::Ping { request_id } => Response::Pong {
+                version: PROTOCOL_VERSION,
+                request_id: self.request_id(request_id),
+            },
+            ClientMessage::Subscribe { request_id, topics } => {
+                self.topics.extend(topics);
+                let mut topics: Vec<Topic> = self.topics.iter().copied().collect();
+                topics.sort();
+                Response::Subscribed {
+                    version: PROTOCOL_VERSION,
+                    request_id: self.request_id(request_id),
+                    topics,
+                }
+            }
+        };
+        Some(response)
+    }
+}
+
+/// Waits for the next logged catch, or forever when not subscribed.
+async fn next_catch(events: &mut Option<broadcast::Receiver<Catch>>) -> Result<Catch, RecvError> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn handle_socket(socket: WebSocket, state: AppState, context: FrameContext) {
+    info!("WebSocket connected");
+    state.metrics.websocket_opened();
+    let (mut sink, mut stream) = socket.split();
+    let (outgoing, mut to_send) = mpsc::channel::<Message>(OUTGOING_CAPACITY);
+    let writer = tokio::spawn(async move {
+        while let Some(message) = to_send.recv().await {
+            if sink.send(message).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let tracker = state
+        .config
+        .tracking
+        .enabled
+        .then(|| Mutex::new(Tracker::new(&state.config.tracking)));
+    let inferences = Arc::new(Semaphore::new(
+        state.config.stream.max_concurrent_inferences,
+    ));
+    let connection = Arc::new(Connection {
+        state,
+        context: Mutex::new(context),
+        tracker,
+        last_frame: Mutex::new(None),
+        pending: Mutex::new(None),
+        frame_ready: Notify::new(),
+        inferences,
+        next_sequence: AtomicU64::new(0),
+        newest_result: Mutex::new(None),
+        dropped_frames: AtomicU64::new(0),
+        outgoing,
+    });
+    let frames = tokio::spawn(connection.clone().run_frames().in_current_span());
+    let mut session = Session {
+        connection: connection.clone(),
+        next_request_id: 0,
+        topics: HashSet::new(),
+    };
+    let mut catch_events = None;
+
+    loop {
+        let msg = tokio::select! {
+            msg = stream.next() => msg,
+            event = next_catch(&mut catch_events) => {
+                match event {
+                    Ok(catch) => {
+                        let response = Response::CatchLogged { version: PROTOCOL_VERSION, catch };
+                        if !connection.send(&response).await {
+                            break;
+                        }
+                    }
+                    // Missing a few events is better than stalling the frames.
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => catch_events = None,
+                }
+                continue;
+            }
+        };
+        let Some(Ok(msg)) = msg else {
+            break;
+        };
+
+        let response = match msg {
+            // Version 1 keepalive
+            Message::Text(text) if text == "ping" => {
+                if connection.outgoing.send("pong".into()).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            // Base64 data URLs from version 1 clients that can only send text
+            Message::Text(text) if text.starts_with("data:image") => {
+                let request_id = session.request_id(None);
+                session.frame(request_id, pipeline::decode_base64(&text))
+            }
+            Message::Text(text) => match parse(&text) {
+                Ok(message) => session.handle(message).await,
+                Err((request_id, code, message)) => {
+                    let request_id = session.request_id(request_id);
+                    Some(Response::failure(request_id, code, message))
+                }
+            },
+            // Raw JPEG/PNG/WebP bytes, which saves the base64 overhead
+            Message::Binary(bytes) => {
+                let request_id = session.request_id(None);
+                session.frame(request_id, Ok(bytes))
+            }
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+
+        if catch_events.is_none() && session.topics.contains(&Topic::Catches) {
+            catch_events = Some(connection.state.catch_events.subscribe());
+        }
+        if let Some(response) = response {
+            if !connection.send(&response).await {
+                break;
+            }
+        }
+    }
+
+    // Inferences already running finish on their own; their results have
+    // nowhere to go once the writer stops.
+    frames.abort();
+    writer.abort();
+    connection.state.metrics.websocket_closed();
+    info!(
+        dropped_frames = connection.dropped_frames.load(Ordering::Relaxed),
+        "WebSocket closed"
+    );
+}
+
+/// `GET /ws?angler_id=&trip_id=`; the ids tie the connection's frames to an
+/// angler's bag limits, and can be changed later with `configure`.
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(trip): Query<TripContext>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    client: Option<Extension<Client>>,
+) -> Result<impl IntoResponse, ApiError> {
+    trips::check_context(&state.db, trip).await?;
+    let context = FrameContext {
+        trip,
+        client: limits::client_id(client.as_ref(), peer),
+        ..Default::default()
+    };
+    let span = info_span!(
+        "connection",
+        id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+        client = %context.client,
+        angler_id = trip.angler_id,
+        trip_id = trip.trip_id,
+    );
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, context).instrument(span)))
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::location::Location;
+
+/// A ring of (longitude, latitude) points, in GeoJSON order.
+type Ring = Vec<(f64, f64)>;
+
+struct Polygon {
+    exterior: Ring,
+    holes: Vec<Ring>,
+}
+
+/// An area where one jurisdiction's regulations apply.
+struct Zone {
+    jurisdiction: String,
+    polygons: Vec<Polygon>,
+}
+
+#[derive(Deserialize)]
+struct FeatureCollection {
+    features: Vec<Feature>,
+}
+
+#[derive(Deserialize)]
+struct Feature {
+    #[serde(default)]
+    properties: serde_json::Map<String, Value>,
+    geometry: Geometry,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "coordinates")]
+enum Geometry {
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPolygon(Vec<Vec<Vec<Vec<f64>>>>),
+}
+
+fn ring(positions: Vec<Vec<f64>>) -> Result<Ring, String> {
+    let ring: Ring = positions
+        .into_iter()
+        .map(|position| match position[..] {
+            [lon, lat, ..] => Ok((lon, lat)),
+            _ => Err("position needs a longitude and a latitude".to_string()),
+        })
+        .collect::<Result<_, _>>()?;
+    if ring.len() < 4 {
+        return Err("polygon ring needs at least four positions".to_string());
+    }
+    Ok(ring)
+}
+
+fn polygon(rings: Vec<Vec<Vec<f64>>>) -> Result<Polygon, String> {
+    let mut rings = rings.into_iter().map(ring);
+    let exterior = rings
+        .next()
+        .ok_or_else(|| "polygon has no rings".to_string())??;
+    Ok(Polygon {
+        exterior,
+        holes: rings.collect::<Result<_, _>>()?,
+    })
+}
+
+/// Even-odd ray casting. Points exactly on an edge may land either side, which
+/// is fine at the scale of a boundary between waters.
+fn ring_contains(ring: &Ring, x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut previous = ring[ring.len() - 1];
+    for &current in ring {
+        let ((x1, y1), (x2, y2)) = (previous, current);
+        if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+impl Polygon {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        ring_contains(&self.exterior, x, y)
+            && !self.holes.iter().any(|hole| ring_contains(hole, x, y))
+    }
+}
+
+/// Regulation zones from a GeoJSON `FeatureCollection` of Polygon and
+/// MultiPolygon features, each with a `jurisdiction` property naming an id in
+/// the regulations file.
+pub struct Zones {
+    zones: Vec<Zone>,
+}
+
+impl Zones {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let invalid = |reason: String| format!("Invalid {}: {}", path.display(), reason);
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let collection: FeatureCollection =
+            serde_json::from_str(&contents).map_err(|err| invalid(err.to_string()))?;
+
+        let mut zones = Vec::new();
+        for (index, feature) in collection.features.into_iter().enumerate() {
+            let jurisdiction = feature
+                .properties
+                .get("jurisdiction")
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "feature {} has no \"jurisdiction\" property",
+                        index
+                    ))
+                })?
+                .to_string();
+            let polygons = match feature.geometry {
+                Geometry::Polygon(rings) => vec![polygon(rings)],
+                Geometry::MultiPolygon(polygons) => polygons.into_iter().map(polygon).collect(),
+            }
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .map_err(|err| invalid(format!("feature {}: {}", index, err)))?;
+            zones.push(Zone {
+                jurisdiction,
+                polygons,
+            });
+        }
+        Ok(Zones { zones })
+    }
+
+    /// Every jurisdiction a zone refers to, so they can be checked at startup.
+    pub fn jurisdictions(&self) -> impl Iterator<Item = &str> {
+        self.zones.iter().map(|zone| zone.jurisdiction.as_str())
+    }
+
+    /// The jurisdiction at `location`. Zones are tried in file order, so list
+    /// smaller waters before any larger zone that encloses them.
+    pub fn lookup(&self, location: &Location) -> Option<&str> {
+        let (x, y) = (location.longitude, location.latitude);
+        self.zones
+            .iter()
+            .find(|zone| zone.polygons.iter().any(|polygon| polygon.contains(x, y)))
+            .map(|zone| zone.jurisdiction.as_str())
+    }
+}